@@ -0,0 +1,1194 @@
+//! Queries behind the language server's navigation requests: what is under
+//! the cursor, where it is defined and what is known about it. They work on
+//! the partial AST of a buffer that does not parse, so navigation keeps
+//! working while typing.
+
+use anyhow::Result;
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use full_moon::parse_fallible;
+
+use crate::config::RuntimeVersion;
+use crate::diagnostic::Location;
+use crate::error::LuascanError;
+use crate::lint::Context;
+use crate::modules;
+use crate::parser;
+use crate::scope::{Access, Member, Require, ScopeTree, SymbolId, SymbolKind, Value};
+use crate::stdlib::{self, Definition};
+
+/// A location in the analysed file (`path` is `None`) or in another one.
+#[derive(Debug, Clone)]
+pub struct FileLocation {
+    pub path: Option<PathBuf>,
+    pub loc: Location,
+}
+
+impl FileLocation {
+    fn here(loc: &Location) -> Self {
+        Self {
+            path: None,
+            loc: loc.clone(),
+        }
+    }
+
+    fn in_file(path: &Path, loc: &Location) -> Self {
+        Self {
+            path: Some(modules::normalize(path)),
+            loc: loc.clone(),
+        }
+    }
+}
+
+/// What the cursor is on.
+#[derive(Debug, Clone, Copy)]
+pub enum Target<'a> {
+    /// The declaration of a local or a reference to it.
+    Local(SymbolId),
+    Global(&'a str),
+    /// A field indexed directly on a name, as `fn` in `M.fn`, or written in
+    /// the table constructor a local is declared with. `symbol` is the local
+    /// holding the table, `None` for a global.
+    Field {
+        symbol: Option<SymbolId>,
+        member: &'a Member,
+    },
+    /// The module name of a `require`.
+    Require(&'a Require),
+}
+
+/// The identity of what the cursor is on, shared by all its occurrences
+/// whichever file they are in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    /// A local of the analysed file.
+    Local(SymbolId),
+    Global(String),
+    /// A field of a table held by a local that the file does not export.
+    LocalField {
+        symbol: SymbolId,
+        name: String,
+    },
+    /// A field of the table returned by the module at `module`.
+    ModuleField {
+        module: PathBuf,
+        name: String,
+    },
+}
+
+impl Key {
+    /// Whether occurrences can only be in the file the key was found in.
+    pub fn is_file_local(&self) -> bool {
+        matches!(self, Self::Local(_) | Self::LocalField { .. })
+    }
+
+    pub fn name<'a>(&'a self, tree: &'a ScopeTree) -> &'a str {
+        match self {
+            Self::Local(id) => &tree.symbols[*id].name,
+            Self::Global(name) | Self::LocalField { name, .. } | Self::ModuleField { name, .. } => {
+                name
+            }
+        }
+    }
+}
+
+/// A name in the source, read or written.
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub loc: Location,
+    pub access: Access,
+    /// Whether this is the `local` declaration or constructor field that
+    /// introduces the name.
+    pub declaration: bool,
+}
+
+impl Occurrence {
+    fn new(loc: &Location, access: Access, declaration: bool) -> Self {
+        Self {
+            loc: loc.clone(),
+            access,
+            declaration,
+        }
+    }
+
+    fn of_member(member: &Member) -> Self {
+        let access = if member.assigned {
+            Access::Write
+        } else {
+            Access::Read
+        };
+        Self::new(&member.loc, access, false)
+    }
+}
+
+pub const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Checks that `name` can stand for a variable or field.
+pub fn validate_name(name: &str) -> Result<()> {
+    if KEYWORDS.contains(&name) {
+        return Err(LuascanError::RenameToKeyword {
+            name: name.to_string(),
+        }
+        .into());
+    }
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(LuascanError::InvalidIdentifier {
+            name: name.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn contains(loc: &Location, offset: usize) -> bool {
+    loc.byte_start <= offset && offset <= loc.byte_end
+}
+
+/// The 1-based line of the byte `offset` of `code`.
+fn line_of(code: &str, offset: usize) -> usize {
+    code.as_bytes()[..offset.min(code.len())]
+        .iter()
+        .filter(|b| **b == b'\n')
+        .count()
+        + 1
+}
+
+/// The `--` comments right above a definition, with their annotations:
+///
+/// ```lua
+/// --- Draws the page.
+/// ---@param theme string the color scheme
+/// ---@return boolean
+/// function M.render(theme) end
+/// ```
+#[derive(Debug, Default)]
+struct DocComment {
+    text: Vec<String>,
+    /// The `@type` annotation.
+    ty: Option<String>,
+    /// `@param` annotations: name, type and description.
+    params: Vec<(String, String, String)>,
+    returns: Option<String>,
+}
+
+impl DocComment {
+    /// The comment above line `line` of `code`.
+    fn above(code: &str, line: usize) -> Self {
+        let lines: Vec<&str> = code.lines().take(line.saturating_sub(1)).collect();
+        let mut ret = Self::default();
+        let comment = lines
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .take_while(|line| line.starts_with("--") && !line.starts_with("--["))
+            .map(|line| line.trim_start_matches('-').trim())
+            .filter(|line| !line.starts_with("luascan:"))
+            .collect::<Vec<_>>();
+        for line in comment.into_iter().rev() {
+            let Some(annotation) = line.strip_prefix('@') else {
+                ret.text.push(line.to_string());
+                continue;
+            };
+            let (tag, rest) = split_word(annotation);
+            match tag {
+                "type" if !rest.is_empty() => ret.ty = Some(rest.to_string()),
+                "param" => {
+                    let (name, rest) = split_word(rest);
+                    let (ty, description) = split_word(rest);
+                    ret.params
+                        .push((name.to_string(), ty.to_string(), description.to_string()));
+                }
+                "return" if !rest.is_empty() => ret.returns = Some(split_word(rest).0.to_string()),
+                _ => {}
+            }
+        }
+        ret
+    }
+
+    /// The part of the comment documenting the parameter `name`.
+    fn param(&self, name: &str) -> Self {
+        let Some((_, ty, description)) = self.params.iter().find(|(n, _, _)| n == name) else {
+            return Self::default();
+        };
+        Self {
+            text: vec![description.clone()],
+            ty: Some(ty.clone()).filter(|ty| !ty.is_empty()),
+            ..Self::default()
+        }
+    }
+
+    fn param_type(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, ty, _)| n == name && !ty.is_empty())
+            .map(|(_, ty, _)| ty.as_str())
+    }
+}
+
+/// The first word of `text` and the rest, both trimmed.
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+/// Markdown describing a name: its kind and type in a code block, where it
+/// is declared and its doc comment.
+fn describe(
+    kind: &str,
+    name: &str,
+    value: Option<&Value>,
+    declaration: Option<&FileLocation>,
+    doc: &DocComment,
+) -> String {
+    let header = match (value, &doc.ty) {
+        (Some(Value::Function { params, .. }), None) => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|param| match doc.param_type(param) {
+                    Some(ty) => format!("{param}: {ty}"),
+                    None => param.clone(),
+                })
+                .collect();
+            let returns = doc
+                .returns
+                .as_ref()
+                .map(|ty| format!(": {ty}"))
+                .unwrap_or_default();
+            format!("({kind}) function {name}({}){returns}", params.join(", "))
+        }
+        (_, Some(ty)) => format!("({kind}) {name}: {ty}"),
+        (Some(value), None) => format!("({kind}) {name}: {value}"),
+        (None, None) => format!("({kind}) {name}"),
+    };
+    let mut parts = vec![format!("```lua\n{header}\n```")];
+    match declaration {
+        Some(FileLocation { path: None, loc }) => {
+            parts.push(format!("Declared at line {}.", loc.line_start));
+        }
+        Some(FileLocation {
+            path: Some(path),
+            loc,
+        }) => parts.push(format!(
+            "Declared in `{}` at line {}.",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            loc.line_start
+        )),
+        None => {}
+    }
+    let text = doc.text.join("\n");
+    if !text.trim().is_empty() {
+        parts.push(text.trim().to_string());
+    }
+    parts.join("\n\n")
+}
+
+fn start_of_file() -> Location {
+    Location {
+        line_start: 1,
+        line_end: 1,
+        col_start: 1,
+        col_end: 1,
+        byte_start: 0,
+        byte_end: 0,
+    }
+}
+
+#[derive(Debug)]
+pub struct Analysis {
+    pub code: String,
+    pub version: RuntimeVersion,
+    pub tree: ScopeTree,
+}
+
+impl Analysis {
+    pub fn new(code: &str, version: RuntimeVersion) -> Self {
+        let result = parse_fallible(code, parser::lua_version(version));
+        Self {
+            code: code.to_string(),
+            version,
+            tree: ScopeTree::build(result.ast()),
+        }
+    }
+
+    pub fn target_at(&self, offset: usize) -> Option<Target<'_>> {
+        let tree = &self.tree;
+        if let Some(require) = tree.requires.iter().find(|r| contains(&r.loc, offset)) {
+            return Some(Target::Require(require));
+        }
+        for reference in &tree.references {
+            if let Some(member) = &reference.member
+                && contains(&member.loc, offset)
+            {
+                return Some(Target::Field {
+                    symbol: reference.symbol,
+                    member,
+                });
+            }
+            if contains(&reference.loc, offset) {
+                return Some(match reference.symbol {
+                    Some(id) => Target::Local(id),
+                    None => Target::Global(&reference.name),
+                });
+            }
+        }
+        if let Some(member) = tree
+            .export
+            .iter()
+            .flat_map(|export| &export.fields)
+            .find(|field| contains(&field.loc, offset))
+        {
+            return Some(Target::Field {
+                symbol: None,
+                member,
+            });
+        }
+        for (id, symbol) in tree.symbols.iter().enumerate() {
+            if contains(&symbol.loc, offset) {
+                return Some(Target::Local(id));
+            }
+            if let Some(member) = symbol
+                .fields
+                .iter()
+                .flatten()
+                .find(|field| contains(&field.loc, offset))
+            {
+                return Some(Target::Field {
+                    symbol: Some(id),
+                    member,
+                });
+            }
+        }
+        None
+    }
+
+    /// The module a local is bound to with `local m = require("m")`.
+    pub fn required_module(&self, symbol: SymbolId) -> Option<&Require> {
+        self.tree.requires.iter().find(|r| r.symbol == Some(symbol))
+    }
+
+    /// Where the field `name` of the table held by `symbol` is defined: in
+    /// the table constructor, by an assignment, or in the required module.
+    pub fn field_definition(
+        &self,
+        symbol: SymbolId,
+        name: &str,
+        context: &Context,
+    ) -> Option<FileLocation> {
+        self.field(symbol, name, context)
+            .map(|(location, _)| location)
+    }
+
+    /// The field `name` of the table held by `symbol`, with where it is
+    /// defined.
+    pub fn field<'a>(
+        &'a self,
+        symbol: SymbolId,
+        name: &str,
+        context: &'a Context,
+    ) -> Option<(FileLocation, &'a Member)> {
+        if let Some(require) = self.required_module(symbol) {
+            let path = context.resolver.resolve(&require.module)?;
+            let field = context
+                .modules
+                .get(&path)?
+                .exports
+                .as_ref()?
+                .iter()
+                .find(|field| field.name == name)?;
+            return Some((FileLocation::in_file(&path, &field.loc), field));
+        }
+        let tree = &self.tree;
+        tree.symbols[symbol]
+            .fields
+            .iter()
+            .flatten()
+            .chain(
+                tree.references_to(symbol)
+                    .filter_map(|r| r.member.as_ref())
+                    .filter(|m| m.assigned),
+            )
+            .find(|field| field.name == name)
+            .map(|field| (FileLocation::here(&field.loc), field))
+    }
+
+    /// Where the thing at `offset` is defined. Globals can have several
+    /// definitions across the workspace.
+    pub fn definition(
+        &self,
+        offset: usize,
+        path: Option<&Path>,
+        context: &Context,
+    ) -> Vec<FileLocation> {
+        let tree = &self.tree;
+        match self.target_at(offset) {
+            Some(Target::Local(id)) => vec![FileLocation::here(&tree.symbols[id].loc)],
+            Some(Target::Global(name)) => self
+                .global_definitions(name, path, context)
+                .into_iter()
+                .map(|(location, _)| location)
+                .collect(),
+            Some(Target::Field { symbol, member }) => match symbol {
+                _ if member.assigned => vec![FileLocation::here(&member.loc)],
+                Some(id) => self
+                    .field_definition(id, &member.name, context)
+                    .into_iter()
+                    .collect(),
+                None => Vec::new(),
+            },
+            Some(Target::Require(require)) => context
+                .resolver
+                .resolve(&require.module)
+                .map(|path| FileLocation::in_file(&path, &start_of_file()))
+                .into_iter()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Where the global `name` is assigned, with the value assigned there.
+    pub fn global_definitions<'a>(
+        &'a self,
+        name: &str,
+        path: Option<&Path>,
+        context: &'a Context,
+    ) -> Vec<(FileLocation, Option<&'a Value>)> {
+        if let Some(write) = self
+            .tree
+            .globals()
+            .find(|r| r.access == Access::Write && r.name == name)
+        {
+            return vec![(FileLocation::here(&write.loc), write.value.as_ref())];
+        }
+        let current = path.map(modules::normalize);
+        let mut ret: Vec<(FileLocation, Option<&Value>)> = context
+            .modules
+            .iter()
+            .filter(|(module, _)| Some(*module) != current.as_deref())
+            .flat_map(|(module, info)| {
+                info.globals
+                    .iter()
+                    .filter(|g| g.name == name)
+                    .map(move |g| (FileLocation::in_file(module, &g.loc), g.value.as_ref()))
+            })
+            .collect();
+        ret.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+        if ret.is_empty()
+            && let Some((path, loc)) = context.library.global(name)
+        {
+            ret.push((FileLocation::in_file(path, loc), None));
+        }
+        ret
+    }
+
+    /// The identity of the name at `offset` in the file at `path`. Fields of
+    /// globals, such as `string.format`, have none.
+    pub fn key_at(&self, offset: usize, path: Option<&Path>, context: &Context) -> Option<Key> {
+        let tree = &self.tree;
+        let exported = |symbol: Option<SymbolId>, member: &Member| {
+            let export = tree.export.as_ref()?;
+            let is_export = match symbol {
+                Some(id) => export.symbol == Some(id) && tree.exports().is_some(),
+                None => export
+                    .fields
+                    .iter()
+                    .any(|f| f.loc.byte_start == member.loc.byte_start),
+            };
+            path.filter(|_| is_export).map(modules::normalize)
+        };
+        match self.target_at(offset)? {
+            Target::Local(id) => Some(Key::Local(id)),
+            Target::Global(name) => Some(Key::Global(name.to_string())),
+            Target::Field { symbol, member } => {
+                let name = member.name.clone();
+                if let Some(module) = exported(symbol, member) {
+                    return Some(Key::ModuleField { module, name });
+                }
+                let symbol = symbol?;
+                match self.required_module(symbol) {
+                    Some(require) => Some(Key::ModuleField {
+                        module: modules::normalize(&context.resolver.resolve(&require.module)?),
+                        name,
+                    }),
+                    None => Some(Key::LocalField { symbol, name }),
+                }
+            }
+            Target::Require(_) => None,
+        }
+    }
+
+    /// Occurrences of `key` in this file, the one at `path`, in source order.
+    pub fn occurrences(
+        &self,
+        key: &Key,
+        path: Option<&Path>,
+        context: &Context,
+    ) -> Vec<Occurrence> {
+        let tree = &self.tree;
+        let mut ret = Vec::new();
+        match key {
+            Key::Local(id) => {
+                ret.push(Occurrence::new(&tree.symbols[*id].loc, Access::Write, true));
+                ret.extend(
+                    tree.references_to(*id)
+                        .map(|r| Occurrence::new(&r.loc, r.access, false)),
+                );
+            }
+            Key::Global(name) => ret.extend(
+                tree.globals()
+                    .filter(|r| r.name == *name)
+                    .map(|r| Occurrence::new(&r.loc, r.access, false)),
+            ),
+            Key::LocalField { symbol, name } => ret.extend(self.field_occurrences(*symbol, name)),
+            Key::ModuleField { module, name } => {
+                let is_module = path.is_some_and(|path| modules::normalize(path) == *module);
+                if is_module && let Some(export) = &tree.export {
+                    ret.extend(
+                        export
+                            .fields
+                            .iter()
+                            .filter(|f| f.name == *name)
+                            .map(|f| Occurrence::new(&f.loc, Access::Write, true)),
+                    );
+                    if let Some(symbol) = export.symbol {
+                        ret.extend(self.field_occurrences(symbol, name));
+                    }
+                }
+                for require in &tree.requires {
+                    let Some(symbol) = require.symbol else {
+                        continue;
+                    };
+                    let resolved = context.resolver.resolve(&require.module);
+                    if resolved.is_some_and(|p| modules::normalize(&p) == *module) {
+                        ret.extend(self.field_occurrences(symbol, name));
+                    }
+                }
+            }
+        }
+        ret.sort_by_key(|o| o.loc.byte_start);
+        ret
+    }
+
+    fn field_occurrences(&self, symbol: SymbolId, name: &str) -> Vec<Occurrence> {
+        let tree = &self.tree;
+        tree.symbols[symbol]
+            .fields
+            .iter()
+            .flatten()
+            .filter(|f| f.name == name)
+            .map(|f| Occurrence::new(&f.loc, Access::Write, true))
+            .chain(
+                tree.references_to(symbol)
+                    .filter_map(|r| r.member.as_ref())
+                    .filter(|m| m.name == name)
+                    .map(Occurrence::of_member),
+            )
+            .collect()
+    }
+
+    /// Refuses renaming names the workspace does not own: standard globals
+    /// and what `workspace.library` defines.
+    pub fn check_renamable(&self, key: &Key, context: &Context) -> Result<()> {
+        let reason = match key {
+            Key::Local(id) if self.tree.symbols[*id].kind == SymbolKind::ImplicitSelf => {
+                Some("it is the implicit `self` of a method")
+            }
+            Key::Global(name) if stdlib::lookup(name).is_some() => {
+                Some("it is part of the standard library")
+            }
+            Key::Global(name) if context.library.defines_global(name) => {
+                Some("it is defined by a library")
+            }
+            Key::ModuleField { module, .. } if context.library.contains(module) => {
+                Some("it is defined by a library")
+            }
+            _ => None,
+        };
+        match reason {
+            Some(reason) => Err(LuascanError::NotRenamable {
+                name: key.name(&self.tree).to_string(),
+                reason,
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that replacing `occurrences` of `key` with `new_name` keeps
+    /// every other name of the file bound as before: the new name must not
+    /// already be taken, capture a reference or shadow a declaration. A
+    /// global must not take the name of a standard library, library or
+    /// workspace global either, even one the file does not mention.
+    pub fn check_rename(
+        &self,
+        key: &Key,
+        occurrences: &[Occurrence],
+        new_name: &str,
+        path: Option<&Path>,
+        context: &Context,
+    ) -> Result<()> {
+        let renamed = match key {
+            Key::Local(_) => None,
+            Key::Global(_) => Some(Key::Global(new_name.to_string())),
+            Key::LocalField { symbol, .. } => Some(Key::LocalField {
+                symbol: *symbol,
+                name: new_name.to_string(),
+            }),
+            Key::ModuleField { module, .. } => Some(Key::ModuleField {
+                module: module.clone(),
+                name: new_name.to_string(),
+            }),
+        };
+        let exists = |key: &Key| !self.occurrences(key, path, context).is_empty();
+        let defined = |name: &str| {
+            stdlib::lookup(name).is_some_and(|d| d.is_available(self.version))
+                || context.library.defines_global(name)
+                || context
+                    .modules
+                    .iter()
+                    .any(|(_, module)| module.globals.iter().any(|g| g.name == name))
+        };
+        if renamed.as_ref().is_some_and(exists)
+            || (matches!(key, Key::Global(_)) && defined(new_name))
+        {
+            return Err(LuascanError::RenameExists {
+                name: new_name.to_string(),
+            }
+            .into());
+        }
+        if matches!(key, Key::LocalField { .. } | Key::ModuleField { .. }) {
+            return Ok(());
+        }
+        // Renaming an identifier keeps the shape of the tree, so symbols and
+        // references of both trees line up by index.
+        let edited = apply_edits(&self.code, occurrences, new_name);
+        let result = parse_fallible(&edited, parser::lua_version(self.version));
+        let tree = ScopeTree::build(result.ast());
+        let old = &self.tree;
+        if let Some((_, r)) = old
+            .references
+            .iter()
+            .zip(&tree.references)
+            .find(|(before, after)| before.symbol != after.symbol)
+        {
+            return Err(LuascanError::RenameCaptures {
+                name: new_name.to_string(),
+                line: r.loc.line_start,
+            }
+            .into());
+        }
+        if let Some((_, s)) = old
+            .symbols
+            .iter()
+            .zip(&tree.symbols)
+            .find(|(before, after)| before.shadows != after.shadows)
+        {
+            return Err(LuascanError::RenameShadows {
+                name: new_name.to_string(),
+                line: s.loc.line_start,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Where the table held by the thing at `offset` is built: the table a
+    /// required module returns, or the constructor a local was declared with.
+    pub fn type_definition(&self, offset: usize, context: &Context) -> Option<FileLocation> {
+        let module = |require: &Require| {
+            let path = context.resolver.resolve(&require.module)?;
+            let loc = context
+                .modules
+                .get(&path)
+                .and_then(|module| module.returns.clone())
+                .unwrap_or_else(start_of_file);
+            Some(FileLocation::in_file(&path, &loc))
+        };
+        match self.target_at(offset)? {
+            Target::Require(require) => module(require),
+            Target::Local(id) => match self.required_module(id) {
+                Some(require) => module(require),
+                None => self.tree.symbols[id]
+                    .fields
+                    .is_some()
+                    .then(|| FileLocation::here(&self.tree.symbols[id].loc)),
+            },
+            _ => None,
+        }
+    }
+
+    /// Markdown describing the name at `offset`, with the range of the name.
+    pub fn hover(
+        &self,
+        offset: usize,
+        path: Option<&Path>,
+        context: &Context,
+    ) -> Option<(Location, String)> {
+        let tree = &self.tree;
+        Some(match self.target_at(offset)? {
+            Target::Local(id) => {
+                let symbol = &tree.symbols[id];
+                let range = tree
+                    .references_to(id)
+                    .map(|r| &r.loc)
+                    .find(|loc| contains(loc, offset))
+                    .unwrap_or(&symbol.loc);
+                let kind = match symbol.kind {
+                    _ if self.tree.is_upvalue(id, offset) => "upvalue",
+                    SymbolKind::Parameter | SymbolKind::ImplicitSelf => "parameter",
+                    _ => "local",
+                };
+                let mut doc = match symbol.kind {
+                    SymbolKind::Parameter => {
+                        let function = tree.function_of(symbol.scope);
+                        let line = line_of(&self.code, tree.scopes[function].start);
+                        DocComment::above(&self.code, line).param(&symbol.name)
+                    }
+                    SymbolKind::ImplicitSelf => DocComment::default(),
+                    _ => DocComment::above(&self.code, symbol.loc.line_start),
+                };
+                if doc.ty.is_none()
+                    && let Some(require) = self.required_module(id)
+                {
+                    doc.ty = Some(format!("module \"{}\"", require.module));
+                }
+                let declaration = FileLocation::here(&symbol.loc);
+                let markdown = describe(
+                    kind,
+                    &symbol.name,
+                    symbol.value.as_ref(),
+                    Some(&declaration),
+                    &doc,
+                );
+                (range.clone(), markdown)
+            }
+            Target::Global(name) => {
+                let range = tree
+                    .globals()
+                    .map(|r| &r.loc)
+                    .find(|loc| contains(loc, offset))?;
+                let definitions = self.global_definitions(name, path, context);
+                let markdown = match (definitions.first(), stdlib::lookup(name)) {
+                    (Some((location, value)), _) => {
+                        describe("global", name, *value, Some(location), &self.doc(location))
+                    }
+                    (None, Some(definition)) => self.describe_builtin(definition),
+                    (None, None) => describe("global", name, None, None, &DocComment::default()),
+                };
+                (range.clone(), markdown)
+            }
+            Target::Field { symbol, member } => {
+                let owner = match symbol {
+                    Some(id) => Some(tree.symbols[id].name.as_str()),
+                    None => tree
+                        .references
+                        .iter()
+                        .find(|r| {
+                            r.member
+                                .as_ref()
+                                .is_some_and(|m| m.loc.byte_start == member.loc.byte_start)
+                        })
+                        .map(|r| r.name.as_str()),
+                };
+                let name = match owner {
+                    Some(owner) => format!("{owner}.{}", member.name),
+                    None => member.name.clone(),
+                };
+                let definition = match symbol {
+                    _ if member.assigned => Some((FileLocation::here(&member.loc), member)),
+                    Some(id) => self.field(id, &member.name, context),
+                    None => None,
+                };
+                let builtin = stdlib::lookup(&name).filter(|_| symbol.is_none());
+                let markdown = match (definition, builtin) {
+                    (None, Some(definition)) => self.describe_builtin(definition),
+                    (Some((location, field)), _) => describe(
+                        "field",
+                        &name,
+                        field.value.as_ref(),
+                        Some(&location),
+                        &self.doc(&location),
+                    ),
+                    (None, None) => describe("field", &name, None, None, &DocComment::default()),
+                };
+                (member.loc.clone(), markdown)
+            }
+            Target::Require(require) => {
+                let declaration = context
+                    .resolver
+                    .resolve(&require.module)
+                    .map(|path| FileLocation::in_file(&path, &start_of_file()));
+                let markdown = describe(
+                    "module",
+                    &format!("\"{}\"", require.module),
+                    None,
+                    declaration.as_ref(),
+                    &DocComment::default(),
+                );
+                (require.loc.clone(), markdown)
+            }
+        })
+    }
+
+    /// The doc comment above `location`.
+    fn doc(&self, location: &FileLocation) -> DocComment {
+        let code = match &location.path {
+            None => Cow::Borrowed(self.code.as_str()),
+            Some(path) => match fs::read_to_string(path) {
+                Ok(code) => Cow::Owned(code),
+                Err(_) => return DocComment::default(),
+            },
+        };
+        DocComment::above(&code, location.loc.line_start)
+    }
+
+    /// Markdown describing a standard library definition for the configured
+    /// runtime.
+    fn describe_builtin(&self, definition: &Definition) -> String {
+        let kind = if definition.name.contains('.') {
+            "field"
+        } else {
+            "global"
+        };
+        let header = match definition.params {
+            Some(params) => format!("({kind}) function {}({params})", definition.name),
+            None => format!("({kind}) {}", definition.name),
+        };
+        let mut parts = vec![
+            format!("```lua\n{header}\n```"),
+            format!("{}.", definition.doc),
+        ];
+        if !definition.is_available(self.version) {
+            let versions: Vec<String> = definition.versions().map(|v| v.to_string()).collect();
+            parts.push(format!(
+                "Not available in {}; only in {}.",
+                self.version,
+                versions.join(", ")
+            ));
+        }
+        parts.join("\n\n")
+    }
+}
+
+/// `code` with the text of each occurrence replaced by `text`.
+pub fn apply_edits(code: &str, occurrences: &[Occurrence], text: &str) -> String {
+    let mut ret = String::with_capacity(code.len());
+    let mut last = 0;
+    for occurrence in occurrences {
+        ret.push_str(&code[last..occurrence.loc.byte_start]);
+        ret.push_str(text);
+        last = occurrence.loc.byte_end;
+    }
+    ret.push_str(&code[last..]);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::library::Library;
+    use crate::modules::ModuleGraph;
+    use crate::resolver::Resolver;
+    use std::{fs, sync::Arc};
+
+    fn context() -> Context {
+        let config = Config::default();
+        let resolver = Resolver::new(&config, [PathBuf::from("tests/modules")]);
+        let mut modules = ModuleGraph::default();
+        let main = Path::new("tests/modules/main.lua");
+        modules.update(main, &fs::read_to_string(main).unwrap(), &config, &resolver);
+        modules.load_dependencies(&config, &resolver);
+        Context {
+            resolver,
+            modules: Arc::new(modules),
+            ..Context::default()
+        }
+    }
+
+    /// Byte offset just inside the `nth` occurrence of `needle`.
+    fn offset(code: &str, needle: &str, nth: usize) -> usize {
+        code.match_indices(needle).nth(nth).unwrap().0 + 1
+    }
+
+    fn lines(locations: Vec<FileLocation>) -> Vec<(Option<PathBuf>, usize)> {
+        locations
+            .into_iter()
+            .map(|l| (l.path, l.loc.line_start))
+            .collect()
+    }
+
+    #[test]
+    fn test_definition_in_file() {
+        let code = "local M = { a = 1 }\nfunction M.b() return M.a end\nlocal x = M.b()\nprint(x, y)\ny = 1\n";
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        let context = Context::default();
+        let definition =
+            |needle, nth| lines(analysis.definition(offset(code, needle, nth), None, &context));
+        assert_eq!(definition("x", 1), vec![(None, 3)]);
+        assert_eq!(definition(".a", 0), vec![(None, 1)]);
+        assert_eq!(definition(".b", 1), vec![(None, 2)]);
+        assert_eq!(definition("y", 0), vec![(None, 5)]);
+        assert_eq!(definition("print", 0), vec![]);
+    }
+
+    #[test]
+    fn test_definition_across_modules() {
+        let code = fs::read_to_string("tests/modules/main.lua").unwrap();
+        let analysis = Analysis::new(&code, RuntimeVersion::Lua54);
+        let context = context();
+        let ui = Some(modules::normalize(Path::new("tests/modules/ui.lua")));
+        assert_eq!(
+            lines(analysis.definition(offset(&code, "render", 0), None, &context)),
+            vec![(ui.clone(), 5)]
+        );
+        assert_eq!(
+            lines(analysis.definition(offset(&code, "\"ui\"", 0), None, &context)),
+            vec![(ui, 1)]
+        );
+        let type_definition = analysis
+            .type_definition(offset(&code, "ui.render", 0), &context)
+            .unwrap();
+        assert_eq!(type_definition.loc.line_start, 9);
+    }
+
+    fn occurrences(
+        analysis: &Analysis,
+        code: &str,
+        needle: &str,
+        path: Option<&Path>,
+        context: &Context,
+    ) -> Vec<(usize, Access, bool)> {
+        let key = analysis
+            .key_at(offset(code, needle, 0), path, context)
+            .unwrap();
+        analysis
+            .occurrences(&key, path, context)
+            .into_iter()
+            .map(|o| (o.loc.line_start, o.access, o.declaration))
+            .collect()
+    }
+
+    #[test]
+    fn test_occurrences_in_file() {
+        let code = "local n = 0\nlocal t = { a = 1 }\nn = t.a\nt.a = n\nprint(g, t.b)\ng = 1\n";
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        let context = Context::default();
+        assert_eq!(
+            occurrences(&analysis, code, "n =", None, &context),
+            vec![
+                (1, Access::Write, true),
+                (3, Access::Write, false),
+                (4, Access::Read, false),
+            ]
+        );
+        assert_eq!(
+            occurrences(&analysis, code, "a =", None, &context),
+            vec![
+                (2, Access::Write, true),
+                (3, Access::Read, false),
+                (4, Access::Write, false),
+            ]
+        );
+        assert_eq!(
+            occurrences(&analysis, code, "g,", None, &context),
+            vec![(5, Access::Read, false), (6, Access::Write, false)]
+        );
+    }
+
+    #[test]
+    fn test_module_field_occurrences() {
+        let context = context();
+        let main = Path::new("tests/modules/main.lua");
+        let ui = Path::new("tests/modules/ui.lua");
+        let main_code = fs::read_to_string(main).unwrap();
+        let ui_code = fs::read_to_string(ui).unwrap();
+        let main_analysis = Analysis::new(&main_code, RuntimeVersion::Lua54);
+        let ui_analysis = Analysis::new(&ui_code, RuntimeVersion::Lua54);
+        let key = main_analysis
+            .key_at(offset(&main_code, "theme", 0), Some(main), &context)
+            .unwrap();
+        assert_eq!(
+            key,
+            Key::ModuleField {
+                module: modules::normalize(ui),
+                name: "theme".to_string()
+            }
+        );
+        assert_eq!(
+            ui_analysis
+                .key_at(offset(&ui_code, "theme", 0), Some(ui), &context)
+                .as_ref(),
+            Some(&key)
+        );
+        let lines = |analysis: &Analysis, path| {
+            analysis
+                .occurrences(&key, Some(path), &context)
+                .iter()
+                .map(|o| (o.loc.line_start, o.access))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(&main_analysis, main), vec![(5, Access::Read)]);
+        assert_eq!(lines(&ui_analysis, ui), vec![(1, Access::Write)]);
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("renamed_1").is_ok());
+        assert_eq!(
+            validate_name("end").unwrap_err().to_string(),
+            "`end` is a Lua keyword"
+        );
+        assert_eq!(
+            validate_name("1x").unwrap_err().to_string(),
+            "`1x` is not a valid identifier"
+        );
+        assert!(validate_name("a-b").is_err());
+        assert!(validate_name("").is_err());
+    }
+
+    #[test]
+    fn test_check_rename() {
+        let code = "local a = 1\nlocal b = 2\nlocal t = { x = 1, y = 2 }\ndo\n  local c = 3\n  print(a, c, t.x)\nend\nprint(b, g)\n";
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        let context = Context::default();
+        let rename = |needle, new_name| {
+            let key = analysis
+                .key_at(offset(code, needle, 0), None, &context)
+                .unwrap();
+            let occurrences = analysis.occurrences(&key, None, &context);
+            analysis
+                .check_renamable(&key, &context)
+                .and_then(|_| analysis.check_rename(&key, &occurrences, new_name, None, &context))
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(rename("a =", "d"), Ok(()));
+        assert_eq!(
+            rename("a =", "c"),
+            Err("renaming to `c` would change what the name at line 6 refers to".into())
+        );
+        assert_eq!(
+            rename("b =", "c"),
+            Err("renaming to `c` would shadow or be shadowed by the declaration at line 5".into())
+        );
+        assert_eq!(
+            rename("c =", "a"),
+            Err("renaming to `a` would change what the name at line 6 refers to".into())
+        );
+        assert_eq!(
+            rename("b =", "g"),
+            Err("renaming to `g` would change what the name at line 8 refers to".into())
+        );
+        assert_eq!(rename("x =", "y"), Err("`y` already exists".into()));
+        assert_eq!(rename("x =", "z"), Ok(()));
+        assert_eq!(
+            rename("print", "echo"),
+            Err("`print` cannot be renamed: it is part of the standard library".into())
+        );
+        assert_eq!(rename("g)", "h"), Ok(()));
+        assert_eq!(rename("g)", "print"), Err("`print` already exists".into()));
+        assert_eq!(
+            rename("g)", "string"),
+            Err("`string` already exists".into())
+        );
+        assert_eq!(rename("g)", "setfenv"), Ok(()));
+
+        let config = Config::load_from_dir(Path::new("tests/library")).unwrap();
+        let mut modules = ModuleGraph::default();
+        modules.update(
+            Path::new("other.lua"),
+            "shared = 1\n",
+            &config,
+            &Resolver::default(),
+        );
+        let context = Context {
+            library: Arc::new(Library::load(&config)),
+            modules: Arc::new(modules),
+            ..Context::default()
+        };
+        let key = analysis
+            .key_at(offset(code, "g)", 0), None, &context)
+            .unwrap();
+        let occurrences = analysis.occurrences(&key, None, &context);
+        let rename = |new_name| {
+            analysis
+                .check_rename(&key, &occurrences, new_name, None, &context)
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(rename("host"), Err("`host` already exists".into()));
+        assert_eq!(rename("shared"), Err("`shared` already exists".into()));
+        assert_eq!(rename("h"), Ok(()));
+    }
+
+    #[test]
+    fn test_hover() {
+        let code = "--- Number of retries.\nlocal retries = 3\n---@param name string who to greet\n---@return string\nlocal function greet(name)\n  return \"hi \" .. name .. retries\nend\nlocal t = { label = \"x\" }\nprint(string.format(\"%s\", t.label), greet)\n";
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        let context = Context::default();
+        let hover = |needle, nth| {
+            analysis
+                .hover(offset(code, needle, nth), None, &context)
+                .unwrap()
+                .1
+        };
+        assert_eq!(
+            hover("retries", 2),
+            "```lua\n(upvalue) retries: number\n```\n\nDeclared at line 2.\n\nNumber of retries."
+        );
+        assert_eq!(
+            hover("name", 2),
+            "```lua\n(parameter) name: string\n```\n\nDeclared at line 5.\n\nwho to greet"
+        );
+        assert_eq!(
+            hover("greet", 2),
+            "```lua\n(local) function greet(name: string): string\n```\n\nDeclared at line 5."
+        );
+        assert_eq!(
+            hover("label", 1),
+            "```lua\n(field) t.label: string\n```\n\nDeclared at line 8."
+        );
+        assert_eq!(
+            hover("format", 0),
+            "```lua\n(field) function string.format(formatstring, ...)\n```\n\nFormats its arguments following a printf-style format string."
+        );
+        let old = Analysis::new("warn(\"x\")\n", RuntimeVersion::Lua51);
+        assert!(
+            old.hover(1, None, &context)
+                .unwrap()
+                .1
+                .ends_with("Not available in Lua 5.1; only in Lua 5.4.")
+        );
+    }
+
+    #[test]
+    fn test_library_global_definition() {
+        let config = Config::load_from_dir(Path::new("tests/library")).unwrap();
+        let context = Context {
+            library: Arc::new(Library::load(&config)),
+            ..Context::default()
+        };
+        let code = "return host.call(1)\n";
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        let definition = analysis.definition(offset(code, "host", 0), None, &context);
+        assert!(
+            definition[0]
+                .path
+                .as_ref()
+                .is_some_and(|path| path.ends_with("tests/library/vendor/host.lua"))
+        );
+    }
+}
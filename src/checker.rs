@@ -0,0 +1,251 @@
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tracing::{Level, event};
+
+use crate::{
+    cli::CheckOptions,
+    diagnostic::{LuascanDiagnostic, Severity},
+    error::LuascanError,
+    lint::{self, Context},
+    modules::ModuleGraph,
+    resolver::Resolver,
+    workspace::{self, Workspace},
+};
+
+#[derive(Debug, Clone)]
+pub struct FileDiagnostic {
+    pub path: PathBuf,
+    pub diagnostic: LuascanDiagnostic,
+}
+
+impl fmt::Display for FileDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let loc = &self.diagnostic.loc;
+        write!(
+            f,
+            "{}:{}:{}: {}[{}]: {}",
+            self.path.display(),
+            loc.line_start,
+            loc.col_start,
+            self.diagnostic.severity,
+            self.diagnostic.code,
+            self.diagnostic.msg
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CheckReport {
+    pub files: Vec<PathBuf>,
+    pub sources: HashMap<PathBuf, String>,
+    pub diagnostics: Vec<FileDiagnostic>,
+}
+
+impl CheckReport {
+    pub fn files_checked(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.diagnostic.severity == Severity::Error)
+            .count()
+    }
+}
+
+pub fn run(options: &CheckOptions) -> Result<CheckReport> {
+    // Each file is checked with the config of its closest `.luascan.toml`,
+    // as in the language server, falling back to the one `luascan` was
+    // started with.
+    let mut workspace = Workspace::new(options.config.clone());
+    // A file checked on its own is resolved from its directory, as if that
+    // were the workspace folder; its config's directory is a root as well.
+    let root = if options.target.is_dir() {
+        options.target.as_path()
+    } else {
+        options.target.parent().unwrap_or(Path::new(""))
+    };
+    let roots = vec![root.to_path_buf()];
+    let target_config = workspace.config_for(&options.target)?;
+    let mut files = collect_files(&options.target)?;
+    let cwd = std::env::current_dir().unwrap_or_default();
+    for path in workspace::resolve_paths(target_config.base_dir(), &target_config.runtime.include)?
+    {
+        let path = path
+            .strip_prefix(&cwd)
+            .map(Path::to_path_buf)
+            .unwrap_or(path);
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    let mut checked = Vec::new();
+    for path in files {
+        let config = workspace.config_for(&path)?;
+        let library = workspace.library_for(&config);
+        if !library.contains(&path) {
+            let resolver = Resolver::new(&config, roots.clone());
+            checked.push((path, config, library, resolver));
+        }
+    }
+
+    let mut report = CheckReport::default();
+    let mut modules = ModuleGraph::default();
+    if options.target.is_dir() {
+        modules.mark_indexed(&options.target);
+    }
+    for (path, config, _, resolver) in &checked {
+        let content = fs::read_to_string(path).map_err(|source| LuascanError::SourceIo {
+            path: path.clone(),
+            source,
+        })?;
+        modules.update(path, &content, config, resolver);
+        modules.load_dependencies(config, resolver);
+        report.sources.insert(path.clone(), content);
+    }
+
+    let modules = Arc::new(modules);
+    for (path, config, library, resolver) in checked {
+        let content = &report.sources[&path];
+        event!(Level::INFO, "check {:?}", &path);
+        let context = Context {
+            library,
+            resolver,
+            modules: Arc::clone(&modules),
+        };
+        report.diagnostics.extend(
+            lint::check(content, Some(&path), &config, &context)
+                .into_iter()
+                .map(|diagnostic| FileDiagnostic {
+                    path: path.clone(),
+                    diagnostic,
+                }),
+        );
+        report.files.push(path);
+    }
+    Ok(report)
+}
+
+fn collect_files(target: &Path) -> Result<Vec<PathBuf>> {
+    if target.is_file() {
+        return Ok(vec![target.to_path_buf()]);
+    }
+    if !target.is_dir() {
+        return Err(LuascanError::TargetNotFound {
+            path: target.to_path_buf(),
+        }
+        .into());
+    }
+    workspace::lua_files(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, RuntimeConfig, RuntimeVersion};
+    use crate::diagnostic::RuleCode;
+    use crate::output::{ColorChoice, OutputFormat};
+    use pretty_assertions::assert_eq;
+
+    fn options(target: &str) -> CheckOptions {
+        CheckOptions {
+            target: PathBuf::from(target),
+            format: OutputFormat::Text,
+            color: ColorChoice::Never,
+            config: Config {
+                runtime: RuntimeConfig {
+                    version: RuntimeVersion::Lua54,
+                    ..RuntimeConfig::default()
+                },
+                ..Config::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_check_directory() {
+        let report = run(&options("tests/sample_workspace")).unwrap();
+        assert_eq!(report.files_checked(), 3);
+        assert_eq!(report.error_count(), 0);
+    }
+
+    #[test]
+    fn test_check_file_with_error() {
+        let report = run(&options("tests/invalid/missing_end.lua")).unwrap();
+        assert_eq!(report.files_checked(), 1);
+        assert!(report.error_count() > 0);
+        assert!(
+            report.diagnostics[0]
+                .to_string()
+                .starts_with("tests/invalid/missing_end.lua:")
+        );
+    }
+
+    #[test]
+    fn test_check_file_resolves_from_its_directory() {
+        let report = run(&options("tests/sample_workspace/init.lua")).unwrap();
+        assert_eq!(report.files_checked(), 1);
+        assert!(
+            report
+                .diagnostics
+                .iter()
+                .all(|d| d.diagnostic.code != RuleCode::UnresolvedRequire)
+        );
+    }
+
+    #[test]
+    fn test_check_include_and_library() {
+        let mut options = options("tests/library/src");
+        options.config = Config::load_from_dir(Path::new("tests/library")).unwrap();
+        let report = run(&options).unwrap();
+        assert_eq!(
+            report.files,
+            vec![
+                PathBuf::from("tests/library/src/main.lua"),
+                PathBuf::from("tests/library/scripts/build.lua"),
+            ]
+        );
+        assert!(report.diagnostics.is_empty());
+
+        options.target = PathBuf::from("tests/library");
+        let report = run(&options).unwrap();
+        assert_eq!(report.files_checked(), 2);
+    }
+
+    #[test]
+    fn test_check_module_graph() {
+        let report = run(&options("tests/modules")).unwrap();
+        let diagnostics: Vec<String> = report.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "tests/modules/a.lua:1:19: warning[W012]: circular require: `b` -> `a`",
+                "tests/modules/b.lua:1:19: warning[W012]: circular require: `a` -> `b`",
+                "tests/modules/main.lua:4:4: warning[W011]: module `ui` has no field `initalize_ui`; did you mean `initialize_ui`?",
+                "tests/modules/unused.lua:1:8: info[W013]: module is never required",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_uses_closest_config_per_file() {
+        let mut options = options("tests/multi_root");
+        options.config.runtime.version = RuntimeVersion::Lua51;
+        let report = run(&options).unwrap();
+        assert_eq!(report.files_checked(), 2);
+        // `<const>` only parses with the Lua 5.4 config of `lua54/`.
+        assert_eq!(report.error_count(), 0);
+    }
+
+    #[test]
+    fn test_check_missing_target() {
+        assert!(run(&options("tests/does_not_exist")).is_err());
+    }
+}
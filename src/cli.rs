@@ -3,7 +3,11 @@ use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
-use crate::{config::Config, error::LuascanError};
+use crate::{
+    config::Config,
+    error::LuascanError,
+    output::{ColorChoice, OutputFormat},
+};
 
 #[derive(Debug)]
 pub enum Command {
@@ -14,6 +18,8 @@ pub enum Command {
 #[derive(Debug, Clone)]
 pub struct CheckOptions {
     pub target: PathBuf,
+    pub format: OutputFormat,
+    pub color: ColorChoice,
     pub config: Config,
 }
 
@@ -39,6 +45,12 @@ enum Subcommands {
     Check {
         // Path to a file or directory containing Lua sources
         path: PathBuf,
+        /// Format of the reported diagnostics
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// When to color the text output
+        #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+        color: ColorChoice,
     },
     // Start the Typua language server
     Lsp,
@@ -50,8 +62,14 @@ pub fn parse() -> Result<Command> {
     let config = Config::load_from_dir(&cwd)?;
 
     let command = match cli.command {
-        Subcommands::Check { path } => Command::Check(CheckOptions {
+        Subcommands::Check {
+            path,
+            format,
+            color,
+        } => Command::Check(CheckOptions {
             target: path,
+            format,
+            color,
             config,
         }),
         Subcommands::Lsp => Command::Lsp(LspOptions { config }),
@@ -0,0 +1,494 @@
+//! Candidates for `textDocument/completion`. What is being typed is read
+//! from the text before the cursor, which stays meaningful while the buffer
+//! does not parse; the names come from the scope tree of the partial AST,
+//! the module graph and the standard library.
+
+use std::path::Path;
+
+use crate::analysis::{Analysis, KEYWORDS};
+use crate::lint::Context;
+use crate::modules;
+use crate::scope::{Access, Member, SymbolKind, Value};
+use crate::stdlib::{self, Definition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Variable,
+    Function,
+    Field,
+    Module,
+    Keyword,
+    Snippet,
+}
+
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+    pub documentation: Option<String>,
+    /// Text to insert, in snippet syntax, when it is not the label.
+    pub snippet: Option<&'static str>,
+}
+
+impl Completion {
+    fn new(label: &str, kind: CompletionKind) -> Self {
+        Self {
+            label: label.to_string(),
+            kind,
+            detail: None,
+            documentation: None,
+            snippet: None,
+        }
+    }
+
+    fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// A name holding `value`.
+    fn of_value(label: &str, kind: CompletionKind, value: Option<&Value>) -> Self {
+        match value {
+            Some(value @ Value::Function { .. }) => {
+                Self::new(label, CompletionKind::Function).with_detail(value.to_string())
+            }
+            Some(value) => Self::new(label, kind).with_detail(value.to_string()),
+            None => Self::new(label, kind),
+        }
+    }
+
+    fn of_builtin(label: &str, definition: &Definition, kind: CompletionKind) -> Self {
+        let completion = match definition.params {
+            Some(params) => Self::new(label, CompletionKind::Function)
+                .with_detail(format!("function({params})")),
+            None => Self::new(label, kind),
+        };
+        Self {
+            documentation: Some(format!("{}.", definition.doc)),
+            ..completion
+        }
+    }
+}
+
+/// The completions at the cursor, replacing the partial word that starts at
+/// byte `start`.
+#[derive(Debug, Default)]
+pub struct Completions {
+    pub start: usize,
+    pub items: Vec<Completion>,
+}
+
+impl Completions {
+    fn push(&mut self, completion: Completion) {
+        let exists = completion.kind != CompletionKind::Snippet
+            && self.items.iter().any(|c| c.label == completion.label);
+        if !exists {
+            self.items.push(completion);
+        }
+    }
+}
+
+/// Label, detail and body of the statement snippets.
+const SNIPPETS: &[(&str, &str, &str)] = &[
+    (
+        "function",
+        "function ... end",
+        "function ${1:name}(${2})\n\t$0\nend",
+    ),
+    (
+        "local function",
+        "local function ... end",
+        "local function ${1:name}(${2})\n\t$0\nend",
+    ),
+    (
+        "for",
+        "for i = 1, n do ... end",
+        "for ${1:i} = ${2:1}, ${3:n} do\n\t$0\nend",
+    ),
+    (
+        "for in",
+        "for k, v in pairs(t) do ... end",
+        "for ${1:k}, ${2:v} in ${3:pairs}(${4:t}) do\n\t$0\nend",
+    ),
+    (
+        "if",
+        "if ... then ... end",
+        "if ${1:condition} then\n\t$0\nend",
+    ),
+    (
+        "while",
+        "while ... do ... end",
+        "while ${1:condition} do\n\t$0\nend",
+    ),
+    (
+        "repeat",
+        "repeat ... until",
+        "repeat\n\t$0\nuntil ${1:condition}",
+    ),
+];
+
+/// What the text before the cursor is in the middle of.
+#[derive(Debug, PartialEq, Eq)]
+enum Cursor<'a> {
+    /// The module name of a `require`.
+    Require(&'a str),
+    /// A field of `owner`, after `owner.` or `owner:`.
+    Member {
+        owner: &'a str,
+        method: bool,
+        prefix: &'a str,
+    },
+    /// A name.
+    Name(&'a str),
+}
+
+pub fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Reads `line`, the text of the line before the cursor. `None` in a comment,
+/// in a string other than a module name, or after an expression whose
+/// fields are unknown.
+fn cursor(line: &str) -> Option<Cursor<'_>> {
+    let mut quote: Option<(char, usize)> = None;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(_) if c == '\\' => {
+                chars.next();
+            }
+            Some((open, _)) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some((c, i)),
+            None if line[i..].starts_with("--") => return None,
+            None => {}
+        }
+    }
+    if let Some((_, open)) = quote {
+        let before = line[..open].trim_end();
+        let before = before.strip_suffix('(').unwrap_or(before).trim_end();
+        let is_require = before
+            .strip_suffix("require")
+            .is_some_and(|rest| !rest.ends_with(is_name_char));
+        return is_require.then(|| Cursor::Require(&line[open + 1..]));
+    }
+    let before = line.trim_end_matches(is_name_char);
+    let prefix = &line[before.len()..];
+    if prefix.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let (head, method) = match before.chars().last() {
+        Some('.') if !before.ends_with("..") => (&before[..before.len() - 1], false),
+        Some(':') if !before.ends_with("::") => (&before[..before.len() - 1], true),
+        _ => return Some(Cursor::Name(prefix)),
+    };
+    let rest = head.trim_end_matches(is_name_char);
+    let owner = &head[rest.len()..];
+    let unknown = owner.is_empty()
+        || owner.starts_with(|c: char| c.is_ascii_digit())
+        || rest.ends_with(['.', ':']);
+    (!unknown).then_some(Cursor::Member {
+        owner,
+        method,
+        prefix,
+    })
+}
+
+/// Completions at byte `offset` of the analysed file, the one at `path`.
+pub fn complete(
+    analysis: &Analysis,
+    offset: usize,
+    path: Option<&Path>,
+    context: &Context,
+) -> Completions {
+    let code = &analysis.code;
+    let offset = offset.min(code.len());
+    let line = &code[code[..offset].rfind('\n').map_or(0, |i| i + 1)..offset];
+    let mut ret = Completions::default();
+    match cursor(line) {
+        Some(Cursor::Require(prefix)) => {
+            ret.start = offset - prefix.len();
+            for module in context.resolver.modules() {
+                ret.push(Completion::new(&module, CompletionKind::Module));
+            }
+        }
+        Some(Cursor::Member {
+            owner,
+            method,
+            prefix,
+        }) => {
+            ret.start = offset - prefix.len();
+            members(analysis, owner, method, offset, context, &mut ret);
+        }
+        Some(Cursor::Name(prefix)) => {
+            ret.start = offset - prefix.len();
+            names(analysis, offset, path, context, &mut ret);
+        }
+        None => ret.start = offset,
+    }
+    ret
+}
+
+fn names(
+    analysis: &Analysis,
+    offset: usize,
+    path: Option<&Path>,
+    context: &Context,
+    ret: &mut Completions,
+) {
+    let tree = &analysis.tree;
+    for id in tree.visible_at(offset) {
+        let symbol = &tree.symbols[id];
+        let kind = match symbol.kind {
+            _ if analysis.tree.is_upvalue(id, offset) => "upvalue",
+            SymbolKind::Parameter | SymbolKind::ImplicitSelf => "parameter",
+            _ => "local",
+        };
+        let detail = match &symbol.value {
+            Some(value) => format!("{kind}: {value}"),
+            None => kind.to_string(),
+        };
+        ret.push(
+            Completion::of_value(
+                &symbol.name,
+                CompletionKind::Variable,
+                symbol.value.as_ref(),
+            )
+            .with_detail(detail),
+        );
+    }
+    for name in stdlib::globals(analysis.version) {
+        if let Some(definition) = stdlib::lookup(name) {
+            ret.push(Completion::of_builtin(
+                name,
+                definition,
+                CompletionKind::Variable,
+            ));
+        }
+    }
+    let current = path.map(modules::normalize);
+    let workspace = context
+        .modules
+        .iter()
+        .filter(|(module, _)| Some(*module) != current.as_deref())
+        .flat_map(|(_, module)| &module.globals)
+        .map(|global| (global.name.as_str(), global.value.as_ref()));
+    let file = tree.globals().filter(|r| r.access == Access::Write);
+    for (name, value) in file
+        .map(|r| (r.name.as_str(), r.value.as_ref()))
+        .chain(workspace)
+    {
+        ret.push(
+            Completion::of_value(name, CompletionKind::Variable, value).with_detail(
+                value.map_or("global".to_string(), |value| format!("global: {value}")),
+            ),
+        );
+    }
+    let mut library: Vec<&str> = context.library.globals().collect();
+    library.sort_unstable();
+    for name in library {
+        ret.push(Completion::new(name, CompletionKind::Variable).with_detail("global"));
+    }
+    for keyword in KEYWORDS {
+        ret.push(Completion::new(keyword, CompletionKind::Keyword));
+    }
+    for (label, detail, body) in SNIPPETS {
+        ret.push(Completion {
+            snippet: Some(body),
+            ..Completion::new(label, CompletionKind::Snippet).with_detail(*detail)
+        });
+    }
+}
+
+fn members(
+    analysis: &Analysis,
+    owner: &str,
+    method: bool,
+    offset: usize,
+    context: &Context,
+    ret: &mut Completions,
+) {
+    let tree = &analysis.tree;
+    let mut fields: Vec<&Member> = Vec::new();
+    let mut library = None;
+    match tree
+        .visible_at(offset)
+        .into_iter()
+        .find(|id| tree.symbols[*id].name == owner)
+    {
+        Some(id) => match analysis.required_module(id) {
+            Some(require) => {
+                let exports = context
+                    .resolver
+                    .resolve(&require.module)
+                    .and_then(|path| context.modules.get(&path))
+                    .and_then(|module| module.exports.as_ref());
+                match exports {
+                    Some(exports) => fields.extend(exports),
+                    None => library = Some(require.module.as_str()),
+                }
+            }
+            None => {
+                let symbol = &tree.symbols[id];
+                fields.extend(symbol.fields.iter().flatten());
+                fields.extend(
+                    tree.references_to(id)
+                        .filter_map(|r| r.member.as_ref())
+                        .filter(|m| m.assigned),
+                );
+                if symbol.value == Some(Value::String) {
+                    library = Some("string");
+                }
+            }
+        },
+        None => {
+            fields.extend(
+                tree.globals()
+                    .filter(|r| r.name == owner)
+                    .filter_map(|r| r.member.as_ref())
+                    .filter(|m| m.assigned),
+            );
+            library = Some(owner);
+        }
+    }
+    let callable = |value: Option<&Value>| {
+        !method || value.is_none_or(|value| matches!(value, Value::Function { .. }))
+    };
+    for field in fields {
+        if callable(field.value.as_ref()) {
+            ret.push(Completion::of_value(
+                &field.name,
+                CompletionKind::Field,
+                field.value.as_ref(),
+            ));
+        }
+    }
+    for definition in library
+        .into_iter()
+        .flat_map(|library| stdlib::members(library, analysis.version))
+    {
+        let label = &definition.name[definition.name.rfind('.').map_or(0, |i| i + 1)..];
+        if !method || definition.params.is_some() {
+            ret.push(Completion::of_builtin(
+                label,
+                definition,
+                CompletionKind::Field,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, RuntimeVersion};
+    use crate::modules::ModuleGraph;
+    use crate::resolver::Resolver;
+    use std::{fs, path::PathBuf, sync::Arc};
+
+    #[test]
+    fn test_cursor() {
+        assert_eq!(cursor("  local x = pri"), Some(Cursor::Name("pri")));
+        assert_eq!(
+            cursor("string.fo"),
+            Some(Cursor::Member {
+                owner: "string",
+                method: false,
+                prefix: "fo"
+            })
+        );
+        assert_eq!(
+            cursor("s:"),
+            Some(Cursor::Member {
+                owner: "s",
+                method: true,
+                prefix: ""
+            })
+        );
+        assert_eq!(cursor("x = a ..b"), Some(Cursor::Name("b")));
+        assert_eq!(
+            cursor("local ui = require(\"ui.ut"),
+            Some(Cursor::Require("ui.ut"))
+        );
+        assert_eq!(cursor("require 'a"), Some(Cursor::Require("a")));
+        assert_eq!(cursor("print(\"str"), None);
+        assert_eq!(cursor("x = 1 -- comm"), None);
+        assert_eq!(cursor("a.b.c"), None);
+        assert_eq!(cursor("x = 1."), None);
+    }
+
+    fn labels(code: &str, context: &Context) -> Vec<String> {
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        complete(&analysis, code.len(), None, context)
+            .items
+            .into_iter()
+            .map(|c| c.label)
+            .collect()
+    }
+
+    #[test]
+    fn test_complete_names_in_broken_buffer() {
+        let code = "local top = 1\nlocal function f(param)\n  local inner = 2\n  do local hidden = 3 end\n  local x = ";
+        let labels = labels(code, &Context::default());
+        for expected in ["inner", "param", "f", "top", "print", "while"] {
+            assert!(labels.iter().any(|l| l == expected), "missing {expected}");
+        }
+        assert!(!labels.iter().any(|l| l == "hidden"), "hidden");
+        assert!(!labels.iter().any(|l| l == "x"), "x");
+        assert!(labels.iter().filter(|l| *l == "function").count() == 2);
+
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        let top = complete(&analysis, code.len(), None, &Context::default())
+            .items
+            .into_iter()
+            .find(|c| c.label == "top")
+            .unwrap();
+        assert_eq!(top.detail.as_deref(), Some("upvalue: number"));
+    }
+
+    #[test]
+    fn test_complete_members() {
+        let context = Context::default();
+        let string = labels("local n = string.", &context);
+        assert!(string.iter().any(|l| l == "format") && !string.iter().any(|l| l == "insert"));
+        let table = labels("table.ins", &context);
+        assert!(table.iter().any(|l| l == "insert"));
+        assert_eq!(
+            labels(
+                "local t = { a = 1, f = function() end }\nt.b = true\nt:",
+                &context
+            ),
+            vec!["f"]
+        );
+        assert_eq!(
+            labels("local t = { a = 1 }\nt.b = true\nprint(t.", &context),
+            vec!["a", "b"]
+        );
+        let methods = labels("local s = 'x'\ns:up", &context);
+        assert!(methods.iter().any(|l| l == "upper"));
+    }
+
+    #[test]
+    fn test_complete_modules() {
+        let config = Config::default();
+        let resolver = Resolver::new(&config, [PathBuf::from("tests/modules")]);
+        let mut modules = ModuleGraph::default();
+        let main = Path::new("tests/modules/main.lua");
+        modules.update(main, &fs::read_to_string(main).unwrap(), &config, &resolver);
+        modules.load_dependencies(&config, &resolver);
+        let context = Context {
+            resolver,
+            modules: Arc::new(modules),
+            ..Context::default()
+        };
+        let mut fields = labels("local ui = require(\"ui\")\nui.", &context);
+        fields.sort();
+        assert_eq!(fields, vec!["initialize_ui", "render", "theme"]);
+        let code = "local m = require(\"u";
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        let completions = complete(&analysis, code.len(), None, &context);
+        assert_eq!(completions.start, code.len() - 1);
+        assert!(completions.items.iter().any(|c| c.label == "unused"));
+    }
+}
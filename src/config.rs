@@ -1,14 +1,16 @@
 use anyhow::Result;
 use std::{
-    fs,
+    collections::HashMap,
+    fmt, fs,
     path::{Path, PathBuf},
 };
 
 use serde::Deserialize;
 
+use crate::diagnostic::{RuleCode, Severity};
 use crate::error::LuascanError;
 
-const DEFAULT_CONFIG_FILENAME: &str = ".luascan.toml";
+pub const DEFAULT_CONFIG_FILENAME: &str = ".luascan.toml";
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
@@ -16,11 +18,15 @@ const DEFAULT_CONFIG_FILENAME: &str = ".luascan.toml";
 pub struct Config {
     pub runtime: RuntimeConfig,
     pub workspace: WorkspaceConfig,
+    pub rules: RulesConfig,
+    /// Directory of the `.luascan.toml` the config was read from.
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
 }
 
 impl Config {
     pub fn load_from_dir(dir: &Path) -> Result<Self> {
-        let path = dir.join(DEFAULT_CONFIG_FILENAME);
+        let path = Self::config_path(dir);
         if !path.exists() {
             return Ok(Self::default());
         }
@@ -34,14 +40,42 @@ impl Config {
             path: path.clone(),
             source,
         })?;
-        let config =
-            toml::from_str(&raw).map_err(|source| LuascanError::ConfigParse { path, source })?;
+        let mut config: Self =
+            toml::from_str(&raw).map_err(|source| LuascanError::ConfigParse {
+                path: path.clone(),
+                source,
+            })?;
+        if let Some(rule) = config.rules.unknown_rule() {
+            return Err(LuascanError::UnknownRule {
+                path,
+                rule: rule.to_string(),
+            }
+            .into());
+        }
+        config.root = path.parent().map(Path::to_path_buf);
         Ok(config)
     }
 
+    /// Directory that relative paths in the config are resolved against: the
+    /// config's own directory, or the current one for the default config.
+    pub fn base_dir(&self) -> &Path {
+        self.root.as_deref().unwrap_or(Path::new(""))
+    }
+
     pub fn config_path(dir: &Path) -> PathBuf {
         dir.join(DEFAULT_CONFIG_FILENAME)
     }
+
+    /// Returns the closest `.luascan.toml` in `path` or one of its ancestors.
+    pub fn find_config_path(path: &Path) -> Option<PathBuf> {
+        path.ancestors()
+            .map(Self::config_path)
+            .find(|candidate| candidate.is_file())
+    }
+
+    pub fn is_config_path(path: &Path) -> bool {
+        path.file_name().and_then(|name| name.to_str()) == Some(DEFAULT_CONFIG_FILENAME)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -49,6 +83,9 @@ impl Config {
 pub struct RuntimeConfig {
     pub version: RuntimeVersion,
     pub include: Vec<String>,
+    /// `;`-separated module templates in the style of `package.path`,
+    /// relative to the workspace roots and library directories.
+    pub path: String,
 }
 
 impl Default for RuntimeConfig {
@@ -56,11 +93,12 @@ impl Default for RuntimeConfig {
         Self {
             version: RuntimeVersion::Luajit,
             include: Vec::new(),
+            path: "?.lua;?/init.lua".to_string(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "lowercase")]
 #[derive(Default)]
 pub enum RuntimeVersion {
@@ -72,9 +110,162 @@ pub enum RuntimeVersion {
     Luajit,
 }
 
+impl fmt::Display for RuntimeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Lua51 => "Lua 5.1",
+            Self::Lua52 => "Lua 5.2",
+            Self::Lua53 => "Lua 5.3",
+            Self::Lua54 => "Lua 5.4",
+            Self::Luajit => "LuaJIT",
+        })
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 #[derive(Default)]
 pub struct WorkspaceConfig {
     pub library: Vec<String>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLevel {
+    Off,
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+impl RuleLevel {
+    fn severity(self) -> Option<Severity> {
+        match self {
+            Self::Off => None,
+            Self::Error => Some(Severity::Error),
+            Self::Warning => Some(Severity::Warning),
+            Self::Info => Some(Severity::Info),
+            Self::Hint => Some(Severity::Hint),
+        }
+    }
+}
+
+/// Options of a single rule. Each rule only reads the options it knows about.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleOptions {
+    pub severity: Option<RuleLevel>,
+    /// Longest allowed line for `line-too-long`, counted in characters.
+    pub max: Option<usize>,
+    /// Extra globals accepted by `undefined-global` and `global-assignment`.
+    pub allowed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum RuleSetting {
+    Level(RuleLevel),
+    Options(RuleOptions),
+}
+
+/// The `[rules]` table, keyed by rule name (`unused-local`) or code (`W003`):
+///
+/// ```toml
+/// [rules]
+/// shadowed-local = "off"
+/// W004 = "hint"
+/// line-too-long = { max = 100 }
+/// undefined-global = { severity = "error", allowed = ["vim"] }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct RulesConfig(HashMap<String, RuleSetting>);
+
+impl RulesConfig {
+    fn setting(&self, code: RuleCode) -> Option<&RuleSetting> {
+        self.0.get(code.name()).or_else(|| self.0.get(code.code()))
+    }
+
+    pub fn options(&self, code: RuleCode) -> RuleOptions {
+        match self.setting(code) {
+            Some(RuleSetting::Options(options)) => options.clone(),
+            Some(RuleSetting::Level(level)) => RuleOptions {
+                severity: Some(*level),
+                ..RuleOptions::default()
+            },
+            None => RuleOptions::default(),
+        }
+    }
+
+    /// The configured severity of `code`, or `None` when the rule is off.
+    pub fn severity(&self, code: RuleCode) -> Option<Severity> {
+        match self.options(code).severity {
+            Some(level) => level.severity(),
+            None => Some(code.default_severity()),
+        }
+    }
+
+    pub fn unknown_rule(&self) -> Option<&str> {
+        self.0
+            .keys()
+            .find(|key| RuleCode::lookup(key).is_none())
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        let config: Config = toml::from_str(
+            r#"
+[rules]
+shadowed-local = "off"
+W004 = "hint"
+undefined-global = { severity = "error", allowed = ["vim"] }
+"#,
+        )
+        .unwrap();
+        let rules = &config.rules;
+        assert_eq!(rules.severity(RuleCode::ShadowedLocal), None);
+        assert_eq!(
+            rules.severity(RuleCode::UnusedParameter),
+            Some(Severity::Hint)
+        );
+        assert_eq!(
+            rules.severity(RuleCode::UndefinedGlobal),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            rules.severity(RuleCode::UnusedLocal),
+            Some(Severity::Warning)
+        );
+        assert_eq!(
+            rules.options(RuleCode::UndefinedGlobal).allowed,
+            vec!["vim"]
+        );
+        assert_eq!(rules.unknown_rule(), None);
+    }
+
+    #[test]
+    fn test_unknown_rule() {
+        let dir = std::env::temp_dir().join("luascan-test-unknown-rule");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            Config::config_path(&dir),
+            "[rules]\nunused-locals = \"off\"\n",
+        )
+        .unwrap();
+        let err = Config::load_from_dir(&dir).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "unknown rule `unused-locals` in {}",
+                Config::config_path(&dir).display()
+            )
+        );
+    }
+}
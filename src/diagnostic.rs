@@ -0,0 +1,342 @@
+use std::{fmt, path::PathBuf};
+
+use full_moon::tokenizer::Position;
+
+const RULES_DOC_URL: &str = "https://github.com/takeshid/luascan/blob/main/docs/rules.md";
+
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub line_start: usize,
+    pub line_end: usize,
+    pub col_start: usize,
+    pub col_end: usize,
+    pub byte_start: usize,
+    pub byte_end: usize,
+}
+
+impl Location {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self {
+            line_start: start.line(),
+            line_end: end.line(),
+            col_start: start.character(),
+            col_end: end.character(),
+            byte_start: start.bytes(),
+            byte_end: end.bytes(),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.line_start, self.col_start, self.line_end, self.col_end
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+            Self::Hint => "hint",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Stable identifier of a check. The code and name are part of the public
+/// interface: they appear in reports, suppressions and `.luascan.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleCode {
+    UnexpectedToken,
+    MalformedToken,
+    NotEqualOperator,
+    AssignmentInCondition,
+    CompoundAssignment,
+    UnclosedBlock,
+    UndefinedGlobal,
+    GlobalAssignment,
+    UnusedLocal,
+    UnusedParameter,
+    ShadowedLocal,
+    LineTooLong,
+    UnusedSuppression,
+    UnknownSuppression,
+    VersionMismatch,
+    UnresolvedRequire,
+    UnknownModuleField,
+    CircularRequire,
+    UnusedModule,
+}
+
+impl RuleCode {
+    pub const ALL: &[RuleCode] = &[
+        RuleCode::UnexpectedToken,
+        RuleCode::MalformedToken,
+        RuleCode::NotEqualOperator,
+        RuleCode::AssignmentInCondition,
+        RuleCode::CompoundAssignment,
+        RuleCode::UnclosedBlock,
+        RuleCode::UndefinedGlobal,
+        RuleCode::GlobalAssignment,
+        RuleCode::UnusedLocal,
+        RuleCode::UnusedParameter,
+        RuleCode::ShadowedLocal,
+        RuleCode::LineTooLong,
+        RuleCode::UnusedSuppression,
+        RuleCode::UnknownSuppression,
+        RuleCode::VersionMismatch,
+        RuleCode::UnresolvedRequire,
+        RuleCode::UnknownModuleField,
+        RuleCode::CircularRequire,
+        RuleCode::UnusedModule,
+    ];
+
+    /// Finds a rule by its code (`W003`) or name (`unused-local`).
+    pub fn lookup(key: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|code| code.code() == key || code.name() == key)
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::UnexpectedToken => "E001",
+            Self::MalformedToken => "E002",
+            Self::NotEqualOperator => "E003",
+            Self::AssignmentInCondition => "E004",
+            Self::CompoundAssignment => "E005",
+            Self::UnclosedBlock => "E006",
+            Self::UndefinedGlobal => "W001",
+            Self::GlobalAssignment => "W002",
+            Self::UnusedLocal => "W003",
+            Self::UnusedParameter => "W004",
+            Self::ShadowedLocal => "W005",
+            Self::LineTooLong => "W006",
+            Self::UnusedSuppression => "W007",
+            Self::UnknownSuppression => "W008",
+            Self::VersionMismatch => "W009",
+            Self::UnresolvedRequire => "W010",
+            Self::UnknownModuleField => "W011",
+            Self::CircularRequire => "W012",
+            Self::UnusedModule => "W013",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UnexpectedToken => "unexpected-token",
+            Self::MalformedToken => "malformed-token",
+            Self::NotEqualOperator => "not-equal-operator",
+            Self::AssignmentInCondition => "assignment-in-condition",
+            Self::CompoundAssignment => "compound-assignment",
+            Self::UnclosedBlock => "unclosed-block",
+            Self::UndefinedGlobal => "undefined-global",
+            Self::GlobalAssignment => "global-assignment",
+            Self::UnusedLocal => "unused-local",
+            Self::UnusedParameter => "unused-parameter",
+            Self::ShadowedLocal => "shadowed-local",
+            Self::LineTooLong => "line-too-long",
+            Self::UnusedSuppression => "unused-suppression",
+            Self::UnknownSuppression => "unknown-suppression",
+            Self::VersionMismatch => "version-mismatch",
+            Self::UnresolvedRequire => "unresolved-require",
+            Self::UnknownModuleField => "unknown-module-field",
+            Self::CircularRequire => "circular-require",
+            Self::UnusedModule => "unused-module",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::UnexpectedToken => "The parser found a token it did not expect",
+            Self::MalformedToken => "The source contains text that is not a valid Lua token",
+            Self::NotEqualOperator => "`!=` is used instead of Lua's `~=`",
+            Self::AssignmentInCondition => "`=` is used instead of `==` in a condition",
+            Self::CompoundAssignment => "A compound assignment such as `+=` is used",
+            Self::UnclosedBlock => "A block is missing its closing `end` or `until`",
+            Self::UndefinedGlobal => {
+                "A global that is neither standard nor assigned in the file is read"
+            }
+            Self::GlobalAssignment => "A new global variable is assigned",
+            Self::UnusedLocal => "A local variable, local function or loop variable is never read",
+            Self::UnusedParameter => "A function parameter is never read",
+            Self::ShadowedLocal => "A local hides another variable with the same name",
+            Self::LineTooLong => "A line is longer than the configured maximum",
+            Self::UnusedSuppression => "A suppression comment suppresses no diagnostics",
+            Self::UnknownSuppression => "A suppression comment names an unknown directive or rule",
+            Self::VersionMismatch => {
+                "A standard library name is used that the configured runtime does not provide"
+            }
+            Self::UnresolvedRequire => {
+                "A `require` names a module that no runtime.path template finds"
+            }
+            Self::UnknownModuleField => "A field is read that the required module never defines",
+            Self::CircularRequire => "Modules require each other in a cycle",
+            Self::UnusedModule => "A module returns a value but nothing requires it",
+        }
+    }
+
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Self::UnexpectedToken
+            | Self::MalformedToken
+            | Self::NotEqualOperator
+            | Self::AssignmentInCondition
+            | Self::CompoundAssignment
+            | Self::UnclosedBlock => Severity::Error,
+            Self::UndefinedGlobal
+            | Self::GlobalAssignment
+            | Self::UnusedLocal
+            | Self::UnusedParameter
+            | Self::ShadowedLocal
+            | Self::LineTooLong
+            | Self::UnusedSuppression
+            | Self::UnknownSuppression
+            | Self::VersionMismatch
+            | Self::UnresolvedRequire
+            | Self::UnknownModuleField
+            | Self::CircularRequire => Severity::Warning,
+            Self::UnusedModule => Severity::Info,
+        }
+    }
+
+    /// Whether the rule reports code that does not parse. Such errors are
+    /// never suppressed.
+    pub fn is_syntax_error(self) -> bool {
+        self.default_severity() == Severity::Error
+    }
+
+    pub fn docs_url(self) -> String {
+        format!(
+            "{RULES_DOC_URL}#{}-{}",
+            self.code().to_lowercase(),
+            self.name()
+        )
+    }
+}
+
+impl fmt::Display for RuleCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    Unnecessary,
+    Deprecated,
+}
+
+/// Another place that helps explain a diagnostic. `path` is `None` when it
+/// lies in the same file.
+#[derive(Debug, Clone)]
+pub struct RelatedInformation {
+    pub path: Option<PathBuf>,
+    pub loc: Location,
+    pub msg: String,
+}
+
+/// A suggested edit replacing the text at `loc` with `replacement`.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub msg: String,
+    pub loc: Location,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LuascanDiagnostic {
+    pub loc: Location,
+    pub msg: String,
+    pub code: RuleCode,
+    pub severity: Severity,
+    pub related: Vec<RelatedInformation>,
+    pub fixes: Vec<Fix>,
+    pub tags: Vec<Tag>,
+}
+
+impl LuascanDiagnostic {
+    pub fn new(code: RuleCode, loc: Location, msg: impl Into<String>) -> Self {
+        Self {
+            loc,
+            msg: msg.into(),
+            code,
+            severity: code.default_severity(),
+            related: Vec::new(),
+            fixes: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn with_related(mut self, loc: Location, msg: impl Into<String>) -> Self {
+        self.related.push(RelatedInformation {
+            path: None,
+            loc,
+            msg: msg.into(),
+        });
+        self
+    }
+
+    pub fn with_fix(
+        mut self,
+        loc: Location,
+        replacement: impl Into<String>,
+        msg: impl Into<String>,
+    ) -> Self {
+        self.fixes.push(Fix {
+            msg: msg.into(),
+            loc,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    pub fn with_tag(mut self, tag: Tag) -> Self {
+        self.tags.push(tag);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_rule_codes_are_unique() {
+        for (i, a) in RuleCode::ALL.iter().enumerate() {
+            for b in &RuleCode::ALL[i + 1..] {
+                assert_ne!(a.code(), b.code());
+                assert_ne!(a.name(), b.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_docs_url() {
+        assert_eq!(
+            RuleCode::UnexpectedToken.docs_url(),
+            "https://github.com/takeshid/luascan/blob/main/docs/rules.md#e001-unexpected-token"
+        );
+    }
+}
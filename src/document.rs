@@ -0,0 +1,140 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, TextDocumentContentChangeEvent};
+
+use crate::line_index::{LineIndex, PositionEncoding};
+
+/// Text of a document known to the server. `version` is `None` for files
+/// indexed from disk that the client has not opened.
+#[derive(Debug, Clone)]
+pub struct Document {
+    text: Rope,
+    version: Option<i32>,
+}
+
+impl Document {
+    pub fn new(text: &str, version: Option<i32>) -> Self {
+        Self {
+            text: Rope::from_str(text),
+            version,
+        }
+    }
+
+    pub fn version(&self) -> Option<i32> {
+        self.version
+    }
+
+    pub fn text(&self) -> String {
+        self.text.to_string()
+    }
+
+    /// Applies one change event. Returns `None` when its range lies outside
+    /// the document, in which case the text is left untouched.
+    pub fn apply_change(
+        &mut self,
+        change: &TextDocumentContentChangeEvent,
+        encoding: PositionEncoding,
+    ) -> Option<()> {
+        let Some(range) = change.range else {
+            self.text = Rope::from_str(&change.text);
+            return Some(());
+        };
+        let start = self.position_to_char(range.start, encoding)?;
+        let end = self.position_to_char(range.end, encoding)?;
+        if start > end {
+            return None;
+        }
+        self.text.remove(start..end);
+        self.text.insert(start, &change.text);
+        Some(())
+    }
+
+    pub fn set_version(&mut self, version: i32) {
+        self.version = Some(version);
+    }
+
+    /// Converts `position` to a char index, clamping `character` to the end
+    /// of its line as the LSP specification requires.
+    fn position_to_char(&self, position: Position, encoding: PositionEncoding) -> Option<usize> {
+        let line = position.line as usize;
+        if line >= self.text.len_lines() {
+            return None;
+        }
+        let line_index = LineIndex::new(&self.text.line(line).to_string());
+        let offset = line_index.offset(Position::new(0, position.character), encoding)?;
+        Some(
+            self.text
+                .byte_to_char(self.text.line_to_byte(line) + offset),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tower_lsp::lsp_types::Range;
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position::new(start.0, start.1),
+                end: Position::new(end.0, end.1),
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_full_change() {
+        let mut doc = Document::new("local x = 1\n", Some(1));
+        let full = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "local y = 2\n".to_string(),
+        };
+        doc.apply_change(&full, PositionEncoding::Utf16).unwrap();
+        assert_eq!(doc.text(), "local y = 2\n");
+    }
+
+    #[test]
+    fn test_apply_incremental_changes() {
+        let mut doc = Document::new("local x = 1\nprint(x)\n", Some(1));
+        doc.apply_change(&change((0, 6), (0, 7), "count"), PositionEncoding::Utf16)
+            .unwrap();
+        doc.apply_change(&change((1, 6), (1, 7), "count"), PositionEncoding::Utf16)
+            .unwrap();
+        doc.apply_change(
+            &change((2, 0), (2, 0), "return count\n"),
+            PositionEncoding::Utf16,
+        )
+        .unwrap();
+        assert_eq!(doc.text(), "local count = 1\nprint(count)\nreturn count\n");
+    }
+
+    #[test]
+    fn test_apply_change_in_each_encoding() {
+        // "🌙" is 4 bytes in UTF-8, 2 code units in UTF-16 and 1 in UTF-32.
+        let text = "-- 🌙 moon\nlocal x = 1\n";
+        for (encoding, character) in [
+            (PositionEncoding::Utf8, 8),
+            (PositionEncoding::Utf16, 6),
+            (PositionEncoding::Utf32, 5),
+        ] {
+            let mut doc = Document::new(text, Some(1));
+            doc.apply_change(&change((0, character), (0, character + 1), "M"), encoding)
+                .unwrap();
+            assert_eq!(doc.text(), "-- 🌙 Moon\nlocal x = 1\n");
+        }
+    }
+
+    #[test]
+    fn test_apply_change_out_of_range() {
+        let mut doc = Document::new("local x = 1\n", Some(1));
+        assert!(
+            doc.apply_change(&change((5, 0), (5, 1), "y"), PositionEncoding::Utf16)
+                .is_none()
+        );
+        assert_eq!(doc.text(), "local x = 1\n");
+    }
+}
@@ -17,16 +17,45 @@ pub enum LuascanError {
         #[source]
         source: toml::de::Error,
     },
+    #[error("unknown rule `{rule}` in {path}")]
+    UnknownRule { path: PathBuf, rule: String },
     #[error("failed to get current dir path: {source}")]
     CurrentDir {
         #[source]
         source: std::io::Error,
     },
-    #[error("failed to parse lua script: {source}")]
-    ParseError {
+    #[error("failed to read lua script {path}: {source}")]
+    SourceIo {
+        path: PathBuf,
         #[source]
-        source: full_moon::Error,
+        source: std::io::Error,
+    },
+    #[error("no such file or directory: {path}")]
+    TargetNotFound { path: PathBuf },
+    #[error("found {errors} error(s) in {files} file(s)")]
+    CheckFailed { errors: usize, files: usize },
+    #[error("document {uri} is not open")]
+    UnknownDocument { uri: String },
+    #[error("rejected change to {uri}: version {version} is not newer than {current}")]
+    StaleDocumentVersion {
+        uri: String,
+        version: i32,
+        current: i32,
     },
+    #[error("change to {uri} has a range outside the document")]
+    InvalidChangeRange { uri: String },
+    #[error("`{name}` is a Lua keyword")]
+    RenameToKeyword { name: String },
+    #[error("`{name}` is not a valid identifier")]
+    InvalidIdentifier { name: String },
+    #[error("`{name}` cannot be renamed: {reason}")]
+    NotRenamable { name: String, reason: &'static str },
+    #[error("`{name}` already exists")]
+    RenameExists { name: String },
+    #[error("renaming to `{name}` would change what the name at line {line} refers to")]
+    RenameCaptures { name: String, line: usize },
+    #[error("renaming to `{name}` would shadow or be shadowed by the declaration at line {line}")]
+    RenameShadows { name: String, line: usize },
     #[error("failed to start tokio runtime: {source}")]
     Runtime {
         #[source]
@@ -0,0 +1,113 @@
+//! Files listed in `workspace.library`. They are indexed for the globals they
+//! define, as read-only definitions, but never reported on.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    slice,
+};
+
+use tracing::{Level, event};
+
+use crate::config::Config;
+use crate::diagnostic::Location;
+use crate::parser;
+use crate::scope::{Access, ScopeTree};
+use crate::workspace;
+
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    files: Vec<PathBuf>,
+    /// Globals the library defines, each with the file and location of its
+    /// first assignment.
+    globals: HashMap<String, (PathBuf, Location)>,
+}
+
+impl Library {
+    /// Reads the library of `config`. Unreadable entries are logged and
+    /// skipped, so a stale path doesn't stop the check.
+    pub fn load(config: &Config) -> Self {
+        let mut library = Self::default();
+        // Each entry is resolved on its own so that a stale one only loses
+        // its own files.
+        let mut files = Vec::new();
+        for entry in &config.workspace.library {
+            match workspace::resolve_paths(config.base_dir(), slice::from_ref(entry)) {
+                Ok(resolved) => files.extend(resolved),
+                Err(e) => event!(Level::WARN, "failed to resolve workspace.library: {}", e),
+            }
+        }
+        files.sort();
+        files.dedup();
+        for path in files {
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    event!(Level::WARN, "failed to read library {:?}: {}", &path, e);
+                    continue;
+                }
+            };
+            let (ast, _) = parser::parse(&content, config.runtime.version);
+            let tree = ScopeTree::build(&ast);
+            let path = path.canonicalize().unwrap_or(path);
+            for r in tree.globals().filter(|r| r.access == Access::Write) {
+                library
+                    .globals
+                    .entry(r.name.clone())
+                    .or_insert_with(|| (path.clone(), r.loc.clone()));
+            }
+            library.files.push(path);
+        }
+        library
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.files.contains(&path)
+    }
+
+    pub fn defines_global(&self, name: &str) -> bool {
+        self.globals.contains_key(name)
+    }
+
+    pub fn globals(&self) -> impl Iterator<Item = &str> {
+        self.globals.keys().map(String::as_str)
+    }
+
+    /// Where the library first assigns the global `name`.
+    pub fn global(&self, name: &str) -> Option<(&Path, &Location)> {
+        self.globals
+            .get(name)
+            .map(|(path, loc)| (path.as_path(), loc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let config = Config::load_from_dir(Path::new("tests/library")).unwrap();
+        let library = Library::load(&config);
+        assert_eq!(library.files.len(), 1);
+        assert!(library.contains(Path::new("tests/library/vendor/host.lua")));
+        assert!(!library.contains(Path::new("tests/library/src/main.lua")));
+        assert!(library.defines_global("host"));
+        assert!(!library.defines_global("helper"));
+    }
+
+    #[test]
+    fn test_load_skips_missing_entries() {
+        let mut config = Config::load_from_dir(Path::new("tests/library")).unwrap();
+        config.workspace.library = vec!["vendor".to_string(), "stale_dir".to_string()];
+        let library = Library::load(&config);
+        assert!(library.contains(Path::new("tests/library/vendor/host.lua")));
+        assert!(library.defines_global("host"));
+    }
+}
@@ -0,0 +1,162 @@
+use tower_lsp::lsp_types::{Position, PositionEncodingKind, Range};
+
+/// Unit in which the `character` of an LSP `Position` is counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    pub fn from_kind(kind: &PositionEncodingKind) -> Option<Self> {
+        if *kind == PositionEncodingKind::UTF8 {
+            Some(Self::Utf8)
+        } else if *kind == PositionEncodingKind::UTF16 {
+            Some(Self::Utf16)
+        } else if *kind == PositionEncodingKind::UTF32 {
+            Some(Self::Utf32)
+        } else {
+            None
+        }
+    }
+
+    fn units(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+}
+
+/// Maps byte offsets of a source text to LSP positions and back.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            text: text.to_string(),
+            line_starts,
+        }
+    }
+
+    /// Converts a byte offset to a position. Offsets past the end of the text
+    /// or inside a character are moved back to the nearest character boundary.
+    pub fn position(&self, offset: usize, encoding: PositionEncoding) -> Position {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let character: usize = self.text[self.line_starts[line]..offset]
+            .chars()
+            .map(|c| encoding.units(c))
+            .sum();
+        Position::new(line as u32, character as u32)
+    }
+
+    /// Converts a position to a byte offset. A `character` past the end of
+    /// its line is clamped to the end of the line.
+    pub fn offset(&self, position: Position, encoding: PositionEncoding) -> Option<usize> {
+        let start = *self.line_starts.get(position.line as usize)?;
+        let line = self.line(position.line as usize);
+        let mut units = 0;
+        for (i, c) in line.char_indices() {
+            if units >= position.character as usize {
+                return Some(start + i);
+            }
+            units += encoding.units(c);
+        }
+        Some(start + line.len())
+    }
+
+    pub fn range(&self, start: usize, end: usize, encoding: PositionEncoding) -> Range {
+        Range {
+            start: self.position(start, encoding),
+            end: self.position(end, encoding),
+        }
+    }
+
+    /// Text of `line` without its line ending.
+    fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line];
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const TEXT: &str = "-- 日本語のコメント\nlocal moon = \"🌙\" + x\r\nreturn moon\n";
+
+    #[test]
+    fn test_position_in_each_encoding() {
+        let index = LineIndex::new(TEXT);
+        let offset = TEXT.find("+ x").unwrap();
+        assert_eq!(
+            index.position(offset, PositionEncoding::Utf8),
+            Position::new(1, 20)
+        );
+        assert_eq!(
+            index.position(offset, PositionEncoding::Utf16),
+            Position::new(1, 18)
+        );
+        assert_eq!(
+            index.position(offset, PositionEncoding::Utf32),
+            Position::new(1, 17)
+        );
+        let offset = TEXT.find("return").unwrap();
+        assert_eq!(
+            index.position(offset, PositionEncoding::Utf16),
+            Position::new(2, 0)
+        );
+    }
+
+    #[test]
+    fn test_offset_round_trip() {
+        let index = LineIndex::new(TEXT);
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            // The '\n' of a "\r\n" has no position of its own.
+            for (offset, _) in TEXT
+                .char_indices()
+                .filter(|(offset, _)| !TEXT[..*offset].ends_with('\r'))
+            {
+                let position = index.position(offset, encoding);
+                assert_eq!(index.offset(position, encoding), Some(offset));
+            }
+        }
+    }
+
+    #[test]
+    fn test_offset_clamps_to_line_end() {
+        let index = LineIndex::new(TEXT);
+        let line_end = TEXT.find('\r').unwrap();
+        assert_eq!(
+            index.offset(Position::new(1, 100), PositionEncoding::Utf16),
+            Some(line_end)
+        );
+        assert_eq!(
+            index.offset(Position::new(9, 0), PositionEncoding::Utf16),
+            None
+        );
+    }
+}
@@ -0,0 +1,663 @@
+//! Semantic checks on top of the scope tree. They only run on files that parse
+//! cleanly, since a partial AST produces misleading scopes.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::{Config, RulesConfig, RuntimeVersion};
+use crate::diagnostic::{Location, LuascanDiagnostic, RuleCode, Tag};
+use crate::library::Library;
+use crate::modules::{self, ModuleGraph};
+use crate::parser;
+use crate::resolver::Resolver;
+use crate::scope::{Access, Reference, ScopeTree, SymbolKind};
+use crate::stdlib;
+use crate::suppression::Suppressions;
+
+const DEFAULT_MAX_LINE_LENGTH: usize = 120;
+
+/// What a check knows beyond the file itself.
+#[derive(Debug, Default)]
+pub struct Context {
+    pub library: Arc<Library>,
+    pub resolver: Resolver,
+    pub modules: Arc<ModuleGraph>,
+}
+
+/// Checks `code`, the content of the file at `path` when it has one.
+pub fn check(
+    code: &str,
+    path: Option<&Path>,
+    config: &Config,
+    context: &Context,
+) -> Vec<LuascanDiagnostic> {
+    let rules = &config.rules;
+    let suppressions = Suppressions::parse(code, config.runtime.version);
+    let (ast, diagnostics) = parser::parse(code, config.runtime.version);
+    // Rules are applied once suppressions are, so that the W007 and W008
+    // problems suppression adds are turned off or re-ranked like the rest.
+    if !diagnostics.is_empty() {
+        return apply_rules(rules, suppressions.apply_to_syntax_errors(diagnostics));
+    }
+    let tree = ScopeTree::build(&ast);
+    let mut ret = Vec::new();
+    ret.extend(globals(
+        &tree,
+        config.runtime.version,
+        rules,
+        &context.library,
+    ));
+    ret.extend(requires(&tree, config, &context.resolver));
+    ret.extend(module_fields(&tree, context));
+    if let Some(path) = path {
+        ret.extend(circular_requires(&tree, path, context));
+        ret.extend(unused_module(&tree, path, context));
+    }
+    ret.extend(unused(&tree));
+    ret.extend(shadowing(&tree));
+    ret.extend(long_lines(code, rules));
+    ret.sort_by_key(|d| d.loc.byte_start);
+    apply_rules(rules, suppressions.apply(ret))
+}
+
+/// Drops diagnostics of rules turned off and applies configured severities.
+fn apply_rules(rules: &RulesConfig, diagnostics: Vec<LuascanDiagnostic>) -> Vec<LuascanDiagnostic> {
+    diagnostics
+        .into_iter()
+        .filter_map(|mut d| {
+            d.severity = rules.severity(d.code)?;
+            Some(d)
+        })
+        .collect()
+}
+
+fn globals(
+    tree: &ScopeTree,
+    version: RuntimeVersion,
+    rules: &RulesConfig,
+    library: &Library,
+) -> Vec<LuascanDiagnostic> {
+    let known = stdlib::globals(version);
+    let allowed_reads = rules.options(RuleCode::UndefinedGlobal).allowed;
+    let allowed_writes = rules.options(RuleCode::GlobalAssignment).allowed;
+    let defined: HashSet<&str> = tree
+        .globals()
+        .filter(|r| r.access == Access::Write)
+        .map(|r| r.name.as_str())
+        .collect();
+    // Only the first write introduces a global; later ones, and writes to
+    // globals the runtime or library already has, update an existing one.
+    let mut assigned: HashSet<&str> = HashSet::new();
+    let mut ret = Vec::new();
+    for reference in tree.globals() {
+        let name = reference.name.as_str();
+        match reference.access {
+            Access::Write
+                if !assigned.insert(name)
+                    || known.contains(&name)
+                    || library.defines_global(name)
+                    || allowed_writes.iter().any(|a| a == name) => {}
+            Access::Write => ret.push(LuascanDiagnostic::new(
+                RuleCode::GlobalAssignment,
+                reference.loc.clone(),
+                format!("assignment to global `{name}`; declare it with `local` instead"),
+            )),
+            Access::Read
+                if defined.contains(name)
+                    || library.defines_global(name)
+                    || allowed_reads.iter().any(|a| a == name) => {}
+            Access::Read => {
+                if let Some(d) = version_mismatch(reference, version) {
+                    ret.push(d);
+                } else if !known.contains(&name) {
+                    ret.push(LuascanDiagnostic::new(
+                        RuleCode::UndefinedGlobal,
+                        reference.loc.clone(),
+                        format!("undefined global `{name}`"),
+                    ));
+                }
+            }
+        }
+    }
+    ret
+}
+
+fn requires(tree: &ScopeTree, config: &Config, resolver: &Resolver) -> Vec<LuascanDiagnostic> {
+    let allowed = config.rules.options(RuleCode::UnresolvedRequire).allowed;
+    tree.requires
+        .iter()
+        .filter(|r| {
+            !stdlib::is_builtin_module(&r.module, config.runtime.version)
+                && !allowed.contains(&r.module)
+                && resolver.resolve(&r.module).is_none()
+        })
+        .map(|r| {
+            LuascanDiagnostic::new(
+                RuleCode::UnresolvedRequire,
+                r.loc.clone(),
+                format!(
+                    "module `{}` not found with runtime.path `{}`",
+                    r.module, config.runtime.path
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Reports fields read from a required module that its returned table lacks.
+fn module_fields(tree: &ScopeTree, context: &Context) -> Vec<LuascanDiagnostic> {
+    let mut ret = Vec::new();
+    for require in &tree.requires {
+        let Some(symbol) = require.symbol else {
+            continue;
+        };
+        let Some(exports) = context
+            .resolver
+            .resolve(&require.module)
+            .and_then(|path| context.modules.get(&path)?.exports.as_ref())
+        else {
+            continue;
+        };
+        for member in tree.references_to(symbol).filter_map(|r| r.member.as_ref()) {
+            if member.assigned || exports.iter().any(|field| field.name == member.name) {
+                continue;
+            }
+            let msg = format!("module `{}` has no field `{}`", require.module, member.name);
+            let names = exports.iter().map(|field| field.name.as_str());
+            let diagnostic = match closest(&member.name, names) {
+                Some(name) => LuascanDiagnostic::new(
+                    RuleCode::UnknownModuleField,
+                    member.loc.clone(),
+                    format!("{msg}; did you mean `{name}`?"),
+                )
+                .with_fix(
+                    member.loc.clone(),
+                    name,
+                    format!("replace with `{name}`"),
+                ),
+                None => {
+                    LuascanDiagnostic::new(RuleCode::UnknownModuleField, member.loc.clone(), msg)
+                }
+            };
+            ret.push(diagnostic);
+        }
+    }
+    ret
+}
+
+/// The candidate within a small edit distance of `name`, if any.
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Reports each `require` of a module that requires the checked file back.
+fn circular_requires(tree: &ScopeTree, path: &Path, context: &Context) -> Vec<LuascanDiagnostic> {
+    let path = modules::normalize(path);
+    let mut ret = Vec::new();
+    for require in &tree.requires {
+        let Some(target) = context.resolver.resolve(&require.module) else {
+            continue;
+        };
+        let chain = if modules::normalize(&target) == path {
+            Some(Vec::new())
+        } else {
+            context.modules.path_between(&target, &path)
+        };
+        if let Some(chain) = chain {
+            let chain: Vec<String> = std::iter::once(require.module.as_str())
+                .chain(chain)
+                .map(|module| format!("`{module}`"))
+                .collect();
+            ret.push(LuascanDiagnostic::new(
+                RuleCode::CircularRequire,
+                require.loc.clone(),
+                format!("circular require: {}", chain.join(" -> ")),
+            ));
+        }
+    }
+    ret
+}
+
+/// Reports a module that nothing in the project requires. Entry points, such
+/// as `init.lua` at a workspace root, are left alone.
+fn unused_module(tree: &ScopeTree, path: &Path, context: &Context) -> Vec<LuascanDiagnostic> {
+    let Some(export) = &tree.export else {
+        return Vec::new();
+    };
+    let is_entry_point = matches!(
+        path.file_name().and_then(|name| name.to_str()),
+        Some("init.lua" | "main.lua")
+    ) && path
+        .parent()
+        .is_some_and(|dir| context.resolver.is_root(dir));
+    if !context.modules.is_complete_for(path)
+        || is_entry_point
+        || context.modules.dependents(path).next().is_some()
+    {
+        return Vec::new();
+    }
+    vec![
+        LuascanDiagnostic::new(
+            RuleCode::UnusedModule,
+            export.loc.clone(),
+            "module is never required",
+        )
+        .with_tag(Tag::Unnecessary),
+    ]
+}
+
+/// Reports a standard global or library member read in a runtime that lacks
+/// it, such as `setfenv` on Lua 5.4 or `table.unpack` on Lua 5.1.
+fn version_mismatch(reference: &Reference, version: RuntimeVersion) -> Option<LuascanDiagnostic> {
+    let global = stdlib::lookup(&reference.name)?;
+    let (definition, loc) = if global.is_available(version) {
+        let member = reference.member.as_ref()?;
+        let definition = stdlib::lookup(&format!("{}.{}", reference.name, member.name))?;
+        let loc = Location {
+            line_end: member.loc.line_end,
+            col_end: member.loc.col_end,
+            byte_end: member.loc.byte_end,
+            ..reference.loc.clone()
+        };
+        (definition, loc)
+    } else {
+        (global, reference.loc.clone())
+    };
+    if definition.is_available(version) {
+        return None;
+    }
+    let others: Vec<String> = definition.versions().map(|v| v.to_string()).collect();
+    let mut msg = format!(
+        "`{}` is not available in {version}; it exists in {}",
+        definition.name,
+        others.join(", ")
+    );
+    let replacement = stdlib::replacement(definition.name, version);
+    if let Some(replacement) = replacement {
+        msg.push_str(&format!("; use {replacement} instead"));
+    }
+    let mut diagnostic = LuascanDiagnostic::new(RuleCode::VersionMismatch, loc.clone(), msg);
+    if definition.is_removed_in(version) {
+        diagnostic = diagnostic.with_tag(Tag::Deprecated);
+    }
+    Some(match replacement {
+        Some(replacement) if replacement.chars().all(|c| c.is_alphanumeric() || c == '.') => {
+            diagnostic.with_fix(loc, replacement, format!("replace with `{replacement}`"))
+        }
+        _ => diagnostic,
+    })
+}
+
+fn unused(tree: &ScopeTree) -> Vec<LuascanDiagnostic> {
+    let mut ret = Vec::new();
+    for (id, symbol) in tree.symbols.iter().enumerate() {
+        let name = symbol.name.as_str();
+        if symbol.kind == SymbolKind::ImplicitSelf || name.starts_with('_') || tree.is_read(id) {
+            continue;
+        }
+        let assigned = tree.references_to(id).next().is_some();
+        let (code, msg) = match symbol.kind {
+            SymbolKind::Parameter => (
+                RuleCode::UnusedParameter,
+                format!("unused parameter `{name}`"),
+            ),
+            SymbolKind::LoopVariable => (
+                RuleCode::UnusedLocal,
+                format!("unused loop variable `{name}`"),
+            ),
+            SymbolKind::LocalFunction => (
+                RuleCode::UnusedLocal,
+                format!("unused local function `{name}`"),
+            ),
+            _ if assigned => (
+                RuleCode::UnusedLocal,
+                format!("local `{name}` is assigned but never read"),
+            ),
+            _ => (RuleCode::UnusedLocal, format!("unused local `{name}`")),
+        };
+        let mut diagnostic =
+            LuascanDiagnostic::new(code, symbol.loc.clone(), msg).with_tag(Tag::Unnecessary);
+        if matches!(
+            symbol.kind,
+            SymbolKind::Parameter | SymbolKind::LoopVariable
+        ) {
+            diagnostic = diagnostic.with_fix(
+                symbol.loc.clone(),
+                format!("_{name}"),
+                "prefix the name with `_` to mark it as unused",
+            );
+        }
+        ret.push(diagnostic);
+    }
+    ret
+}
+
+fn shadowing(tree: &ScopeTree) -> Vec<LuascanDiagnostic> {
+    tree.symbols
+        .iter()
+        .filter(|symbol| symbol.kind != SymbolKind::ImplicitSelf && !symbol.name.starts_with('_'))
+        .filter_map(|symbol| {
+            let shadowed = &tree.symbols[symbol.shadows?];
+            let msg = if shadowed.scope == symbol.scope {
+                format!("`{}` redeclares a local in the same scope", symbol.name)
+            } else {
+                format!("`{}` shadows an earlier declaration", symbol.name)
+            };
+            Some(
+                LuascanDiagnostic::new(RuleCode::ShadowedLocal, symbol.loc.clone(), msg)
+                    .with_related(shadowed.loc.clone(), "previously declared here"),
+            )
+        })
+        .collect()
+}
+
+fn long_lines(code: &str, rules: &RulesConfig) -> Vec<LuascanDiagnostic> {
+    let max = rules
+        .options(RuleCode::LineTooLong)
+        .max
+        .unwrap_or(DEFAULT_MAX_LINE_LENGTH);
+    let mut ret = Vec::new();
+    let mut offset = 0;
+    for (i, line) in code.split_inclusive('\n').enumerate() {
+        let text = line.trim_end_matches(['\r', '\n']);
+        let len = text.chars().count();
+        if len > max {
+            let overflow = text.char_indices().nth(max).map_or(0, |(b, _)| b);
+            let loc = Location {
+                line_start: i + 1,
+                line_end: i + 1,
+                col_start: max + 1,
+                col_end: len + 1,
+                byte_start: offset + overflow,
+                byte_end: offset + text.len(),
+            };
+            ret.push(LuascanDiagnostic::new(
+                RuleCode::LineTooLong,
+                loc,
+                format!("line is {len} characters long, more than the limit of {max}"),
+            ));
+        }
+        offset += line.len();
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RuntimeConfig;
+    use crate::diagnostic::Severity;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn config(version: RuntimeVersion) -> Config {
+        Config {
+            runtime: RuntimeConfig {
+                version,
+                ..RuntimeConfig::default()
+            },
+            ..Config::default()
+        }
+    }
+
+    fn codes(code: &str, version: RuntimeVersion) -> Vec<(usize, String)> {
+        check(code, None, &config(version), &Context::default())
+            .into_iter()
+            .map(|d| (d.loc.line_start, format!("{} {}", d.code, d.msg)))
+            .collect()
+    }
+
+    #[test]
+    fn test_globals() {
+        let code = "counter = 0\nprint(counter, undefined_name)\nlocal t = table.unpack({})\ncounter = counter + 1\nprint = function() end\n";
+        assert_eq!(
+            codes(code, RuntimeVersion::Lua54),
+            vec![
+                (
+                    1,
+                    "W002 assignment to global `counter`; declare it with `local` instead"
+                        .to_string()
+                ),
+                (2, "W001 undefined global `undefined_name`".to_string()),
+                (3, "W003 unused local `t`".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_known_globals_follow_runtime_version() {
+        let code = "return setfenv, bit, utf8, undefined_name\n";
+        let reported = |version| {
+            check(code, None, &config(version), &Context::default())
+                .into_iter()
+                .map(|d| {
+                    format!(
+                        "{} {}",
+                        d.code,
+                        &d.msg[..d.msg.find(';').unwrap_or(d.msg.len())]
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            reported(RuntimeVersion::Luajit),
+            vec![
+                "W009 `utf8` is not available in LuaJIT",
+                "W001 undefined global `undefined_name`"
+            ]
+        );
+        assert_eq!(
+            reported(RuntimeVersion::Lua54),
+            vec![
+                "W009 `setfenv` is not available in Lua 5.4",
+                "W009 `bit` is not available in Lua 5.4",
+                "W001 undefined global `undefined_name`"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_locals_and_parameters() {
+        let code = "local function f(a, _b, c)\n  local d = 1\n  d = 2\n  for i, v in ipairs(c) do print(v) end\nend\n";
+        assert_eq!(
+            codes(code, RuntimeVersion::Lua54),
+            vec![
+                (1, "W003 unused local function `f`".to_string()),
+                (1, "W004 unused parameter `a`".to_string()),
+                (2, "W003 local `d` is assigned but never read".to_string()),
+                (4, "W003 unused loop variable `i`".to_string()),
+            ]
+        );
+        let diagnostics = check(
+            code,
+            None,
+            &config(RuntimeVersion::Lua54),
+            &Context::default(),
+        );
+        assert_eq!(diagnostics[1].fixes[0].replacement, "_a");
+        assert_eq!(diagnostics[1].tags, vec![Tag::Unnecessary]);
+    }
+
+    #[test]
+    fn test_unused_module_needs_indexed_folder() {
+        let path = Path::new("tests/modules/unused.lua");
+        let code = "return { value = 1 }\n";
+        let config = config(RuntimeVersion::Lua54);
+        let unused = |modules: ModuleGraph| {
+            let context = Context {
+                modules: Arc::new(modules),
+                ..Context::default()
+            };
+            check(code, Some(path), &config, &context)
+                .iter()
+                .any(|d| d.code == RuleCode::UnusedModule)
+        };
+        assert!(!unused(ModuleGraph::default()));
+        let mut modules = ModuleGraph::default();
+        modules.mark_indexed(Path::new("tests/modules"));
+        assert!(unused(modules));
+    }
+
+    #[test]
+    fn test_shadowed_local() {
+        let code = "local x = 1\nlocal function f()\n  local x = 2\n  return x\nend\nreturn f, x\n";
+        let diagnostics = check(
+            code,
+            None,
+            &config(RuntimeVersion::Lua54),
+            &Context::default(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, RuleCode::ShadowedLocal);
+        assert_eq!(diagnostics[0].loc.line_start, 3);
+        assert_eq!(diagnostics[0].related[0].loc.line_start, 1);
+    }
+
+    #[test]
+    fn test_syntax_errors_skip_semantic_checks() {
+        let diagnostics = check(
+            "local x = \n",
+            None,
+            &config(RuntimeVersion::Lua54),
+            &Context::default(),
+        );
+        assert!(
+            diagnostics
+                .iter()
+                .all(|d| d.code == RuleCode::UnexpectedToken)
+        );
+    }
+
+    #[test]
+    fn test_rules_config() {
+        let mut config = config(RuntimeVersion::Lua54);
+        config.rules = toml::from_str(
+            r#"
+unused-local = "off"
+W001 = { severity = "error", allowed = ["vim"] }
+line-too-long = { max = 20, severity = "hint" }
+"#,
+        )
+        .unwrap();
+        let code = "local x = vim.fn(y)\nprint('a line over twenty')\n";
+        let diagnostics: Vec<(String, Severity)> = check(code, None, &config, &Context::default())
+            .into_iter()
+            .map(|d| (d.msg, d.severity))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                ("undefined global `y`".to_string(), Severity::Error),
+                (
+                    "line is 27 characters long, more than the limit of 20".to_string(),
+                    Severity::Hint
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let messages = |code, version| {
+            check(code, None, &config(version), &Context::default())
+                .into_iter()
+                .map(|d| {
+                    let fix = d.fixes.first().map(|f| f.replacement.clone());
+                    (format!("{} {}", d.code, d.msg), fix)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages("return table.unpack({})\n", RuntimeVersion::Lua51),
+            vec![(
+                "W009 `table.unpack` is not available in Lua 5.1; it exists in Lua 5.2, Lua 5.3, Lua 5.4; use unpack instead".to_string(),
+                Some("unpack".to_string())
+            )]
+        );
+        assert_eq!(
+            messages("setfenv(1, {})\n", RuntimeVersion::Lua52),
+            vec![(
+                "W009 `setfenv` is not available in Lua 5.2; it exists in Lua 5.1, LuaJIT; use `_ENV` instead".to_string(),
+                None
+            )]
+        );
+        assert_eq!(
+            messages("return unpack({})\n", RuntimeVersion::Lua54),
+            vec![(
+                "W009 `unpack` is not available in Lua 5.4; it exists in Lua 5.1, LuaJIT; use table.unpack instead".to_string(),
+                Some("table.unpack".to_string())
+            )]
+        );
+        assert_eq!(
+            messages(
+                "return bit.band(1, 3), jit.version, table.move({}, 1, 1, 1)\n",
+                RuntimeVersion::Luajit
+            ),
+            vec![]
+        );
+        assert_eq!(
+            messages("return table.move({}, 1, 1, 1)\n", RuntimeVersion::Lua51),
+            vec![(
+                "W009 `table.move` is not available in Lua 5.1; it exists in Lua 5.3, Lua 5.4, LuaJIT".to_string(),
+                None
+            )]
+        );
+        let tags = |code, version| {
+            check(code, None, &config(version), &Context::default())
+                .into_iter()
+                .flat_map(|d| d.tags)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            tags("return math.pow(2, 3)\n", RuntimeVersion::Lua53),
+            vec![Tag::Deprecated]
+        );
+        assert_eq!(
+            tags("return table.unpack({})\n", RuntimeVersion::Lua51),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_unresolved_require() {
+        let config = config(RuntimeVersion::Lua54);
+        let context = Context {
+            resolver: Resolver::new(&config, [PathBuf::from("tests/sample_workspace")]),
+            ..Context::default()
+        };
+        let code = "local ui = require('ui')\nlocal m = require('missing.mod')\nlocal s = require('string')\nreturn ui, m, s\n";
+        let messages: Vec<String> = check(code, None, &config, &context)
+            .into_iter()
+            .map(|d| format!("{} {}", d.code, d.msg))
+            .collect();
+        assert_eq!(
+            messages,
+            vec!["W010 module `missing.mod` not found with runtime.path `?.lua;?/init.lua`"]
+        );
+    }
+}
@@ -1,71 +1,107 @@
+use crate::analysis::{self, Analysis, FileLocation, Key, Occurrence};
 use crate::cli::LspOptions;
-use crate::parser;
+use crate::completion::{self, CompletionKind};
+use crate::config::{Config, DEFAULT_CONFIG_FILENAME};
+use crate::diagnostic::{Location, LuascanDiagnostic, Severity, Tag};
+use crate::library::Library;
+use crate::line_index::{LineIndex, PositionEncoding};
+use crate::lint::{self, Context};
+use crate::modules::ModuleGraph;
+use crate::outline::{self, OutlineItem, OutlineKind};
+use crate::resolver::Resolver;
+use crate::scope::Access;
+use crate::signature;
+use crate::symbols::IndexedSymbol;
+use crate::workspace::{self, Workspace};
 use anyhow::{Result, anyhow};
-use clap::builder::Str;
-use glob::glob;
 use jsonrpc::Result as LspResult;
 use lsp_types::{
-    Diagnostic, InitializeParams, InitializeResult, InitializedParams, MessageType, OneOf,
-    Position, Range, ServerCapabilities, ServerInfo, TextDocumentSyncCapability,
-    TextDocumentSyncKind, TextDocumentSyncOptions, WorkspaceFoldersServerCapabilities,
-    WorkspaceServerCapabilities,
+    CodeAction, CodeActionKind, CodeActionOptions, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, CodeDescription, CompletionItem,
+    CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    CompletionTextEdit, DeclarationCapability, Diagnostic, DiagnosticRelatedInformation,
+    DiagnosticTag, DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
+    DidChangeWorkspaceFoldersParams, DidCloseTextDocumentParams, DocumentHighlight,
+    DocumentHighlightKind, DocumentHighlightParams, DocumentSymbol, DocumentSymbolParams,
+    DocumentSymbolResponse, Documentation, FileSystemWatcher, GlobPattern, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, InitializeResult, InitializedParams, InsertTextFormat, MarkupContent,
+    MarkupKind, MessageType, NumberOrString, OneOf, ParameterInformation, ParameterLabel,
+    PrepareRenameResponse, ReferenceParams, Registration, RenameOptions, RenameParams,
+    ServerCapabilities, ServerInfo, SignatureHelp, SignatureHelpOptions, SignatureHelpParams,
+    SignatureInformation, SymbolInformation, SymbolKind, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions, TextEdit,
+    TypeDefinitionProviderCapability, WorkDoneProgressOptions, WorkspaceEdit,
+    WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities, WorkspaceSymbolParams,
 };
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use std::time::Instant;
+use tower_lsp::lsp_types::request::{
+    GotoDeclarationParams, GotoDeclarationResponse, GotoTypeDefinitionParams,
+    GotoTypeDefinitionResponse,
+};
 use tower_lsp::lsp_types::{
     DiagnosticSeverity, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
-    DidSaveTextDocumentParams, PositionEncodingKind, Url,
+    DidSaveTextDocumentParams, PositionEncodingKind, TextDocumentContentChangeEvent, Url,
 };
 use tower_lsp::{Client, LanguageServer, LspService, Server, jsonrpc, lsp_types};
 use tracing::{Level, event};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Fix suggestion carried in `Diagnostic::data` and offered as a quick fix.
+#[derive(Debug, Serialize, Deserialize)]
+struct QuickFix {
+    title: String,
+    edit: TextEdit,
+}
+
 pub struct Backend {
     client: Client,
-    root: Arc<RwLock<Option<PathBuf>>>,
-    workspace: Arc<RwLock<HashMap<PathBuf, String>>>,
+    position_encoding: Arc<RwLock<PositionEncoding>>,
+    workspace: Arc<RwLock<Workspace>>,
 }
 
 impl Backend {
-    fn new(client: Client, _: LspOptions) -> Self {
+    fn new(client: Client, options: LspOptions) -> Self {
         Self {
             client,
-            root: Arc::new(RwLock::new(None)),
-            workspace: Arc::new(RwLock::new(HashMap::new())),
+            position_encoding: Arc::new(RwLock::new(PositionEncoding::default())),
+            workspace: Arc::new(RwLock::new(Workspace::new(options.config))),
         }
     }
     async fn check_syntax(&self, uri: Url, content: String) {
         let start = Instant::now();
+        let path = uri.to_file_path().ok();
+        let config = match &path {
+            Some(path) => self.get_config(path).await,
+            None => Config::default(),
+        };
+        let resolver = Resolver::new(&config, self.get_folders().await);
+        let modules = match &path {
+            Some(path) => self.update_module(path, &content, &config, &resolver).await,
+            None => Arc::default(),
+        };
+        let library = self.get_library(&config).await;
+        if path.as_ref().is_some_and(|path| library.contains(path)) {
+            self.client.publish_diagnostics(uri, Vec::new(), None).await;
+            return;
+        }
+        let context = Context {
+            library,
+            resolver,
+            modules,
+        };
+        let encoding = self.get_position_encoding().await;
+        let line_index = LineIndex::new(&content);
         let diagnotics: Vec<Diagnostic> =
-            parser::parse(content.as_str(), crate::config::RuntimeVersion::Lua51)
+            lint::check(content.as_str(), path.as_deref(), &config, &context)
                 .iter()
-                .map(|d| Diagnostic {
-                    range: Range {
-                        start: Position {
-                            line: (d.loc.line_start as u32).saturating_sub(1),
-                            character: (d.loc.col_start as u32).saturating_sub(1),
-                        },
-                        end: Position {
-                            line: (d.loc.line_end as u32).saturating_sub(1),
-                            character: (d.loc.col_end as u32).saturating_sub(1),
-                        },
-                    },
-                    severity: Some(DiagnosticSeverity::ERROR),
-                    message: d.msg.clone(),
-                    code: Some(lsp_types::NumberOrString::String(
-                        "luascan code".to_string(),
-                    )),
-                    code_description: Some(lsp_types::CodeDescription {
-                        href: Url::parse("http://example.com").expect("parse url failed"),
-                    }),
-                    source: Some("luascan source".to_string()),
-                    ..Diagnostic::default()
-                })
+                .map(|d| self.to_lsp_diagnostic(&uri, &line_index, encoding, d))
                 .collect();
         let elapsed = start.elapsed();
         let log_msg = format!(
@@ -82,42 +118,459 @@ impl Backend {
             .publish_diagnostics(uri.clone(), diagnotics.clone(), None)
             .await;
     }
-    async fn set_root(&self, path: PathBuf) -> Result<()> {
+    /// Checks `uri`, then the documents that require it or that it requires,
+    /// since their cross-file diagnostics may have changed with it.
+    async fn check_with_related(&self, uri: Url, content: String) {
+        let path = uri.to_file_path().ok();
+        self.check_syntax(uri, content).await;
+        if let Some(path) = path {
+            self.check_related(&path).await;
+        }
+    }
+    /// Checks the documents that require `path` or that it requires.
+    async fn check_related(&self, path: &Path) {
+        let related = {
+            let ws_ref = Arc::clone(&self.workspace);
+            match ws_ref.read() {
+                Ok(reader) => reader.related_documents(path),
+                Err(_) => Vec::new(),
+            }
+        };
+        for (uri, content) in related {
+            self.check_syntax(uri, content).await;
+        }
+    }
+    fn to_lsp_diagnostic(
+        &self,
+        uri: &Url,
+        line_index: &LineIndex,
+        encoding: PositionEncoding,
+        d: &LuascanDiagnostic,
+    ) -> Diagnostic {
+        let related_information: Vec<DiagnosticRelatedInformation> = d
+            .related
+            .iter()
+            .filter_map(|r| {
+                let location = match &r.path {
+                    None => lsp_types::Location {
+                        uri: uri.clone(),
+                        range: line_index.range(r.loc.byte_start, r.loc.byte_end, encoding),
+                    },
+                    Some(path) => {
+                        let uri = Url::from_file_path(path).ok()?;
+                        let text = self.get_doc_text(&uri)?;
+                        lsp_types::Location {
+                            range: LineIndex::new(&text).range(
+                                r.loc.byte_start,
+                                r.loc.byte_end,
+                                encoding,
+                            ),
+                            uri,
+                        }
+                    }
+                };
+                Some(DiagnosticRelatedInformation {
+                    location,
+                    message: r.msg.clone(),
+                })
+            })
+            .collect();
+        let fixes: Vec<QuickFix> = d
+            .fixes
+            .iter()
+            .map(|f| QuickFix {
+                title: f.msg.clone(),
+                edit: TextEdit {
+                    range: line_index.range(f.loc.byte_start, f.loc.byte_end, encoding),
+                    new_text: f.replacement.clone(),
+                },
+            })
+            .collect();
+        let tags: Vec<DiagnosticTag> = d
+            .tags
+            .iter()
+            .map(|tag| match tag {
+                Tag::Unnecessary => DiagnosticTag::UNNECESSARY,
+                Tag::Deprecated => DiagnosticTag::DEPRECATED,
+            })
+            .collect();
+        Diagnostic {
+            range: line_index.range(d.loc.byte_start, d.loc.byte_end, encoding),
+            severity: Some(match d.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+                Severity::Info => DiagnosticSeverity::INFORMATION,
+                Severity::Hint => DiagnosticSeverity::HINT,
+            }),
+            code: Some(NumberOrString::String(d.code.code().to_string())),
+            code_description: Url::parse(&d.code.docs_url())
+                .ok()
+                .map(|href| CodeDescription { href }),
+            source: Some("luascan".to_string()),
+            message: d.msg.clone(),
+            related_information: (!related_information.is_empty()).then_some(related_information),
+            tags: (!tags.is_empty()).then_some(tags),
+            data: if fixes.is_empty() {
+                None
+            } else {
+                serde_json::to_value(fixes).ok()
+            },
+        }
+    }
+    fn get_doc_text(&self, uri: &Url) -> Option<String> {
+        let ws_ref = Arc::clone(&self.workspace);
+        let reader = ws_ref.read().ok()?;
+        reader
+            .documents()
+            .find(|(doc_uri, _)| *doc_uri == uri)
+            .map(|(_, doc)| doc.text())
+    }
+    async fn add_folder(&self, path: PathBuf) -> Result<()> {
         if path.exists() {
-            let root_ref = Arc::clone(&self.root);
-            if let Ok(mut writer) = root_ref.write() {
-                *writer = Some(path)
+            let ws_ref = Arc::clone(&self.workspace);
+            if let Ok(mut writer) = ws_ref.write() {
+                writer.add_folder(path)
             }
             Ok(())
         } else {
             Err(anyhow!(
-                "failed to set root path. {:?} is not existed.",
+                "failed to add workspace folder. {:?} is not existed.",
                 path
             ))
         }
     }
-    async fn get_root(&self) -> Option<PathBuf> {
-        let root_ref = Arc::clone(&self.root);
-        if let Ok(reader) = root_ref.read() {
-            reader.clone()
+    async fn remove_folder(&self, path: &Path) -> Vec<Url> {
+        let ws_ref = Arc::clone(&self.workspace);
+        if let Ok(mut writer) = ws_ref.write() {
+            writer.remove_folder(path)
+        } else {
+            Vec::new()
+        }
+    }
+    async fn get_folders(&self) -> Vec<PathBuf> {
+        let ws_ref = Arc::clone(&self.workspace);
+        if let Ok(reader) = ws_ref.read() {
+            reader.folders().to_vec()
         } else {
-            None
+            Vec::new()
+        }
+    }
+    async fn get_config(&self, path: &Path) -> Config {
+        let ws_ref = Arc::clone(&self.workspace);
+        let config = match ws_ref.write() {
+            Ok(mut writer) => writer.config_for(path),
+            Err(_) => return Config::default(),
+        };
+        match config {
+            Ok(config) => config,
+            Err(e) => {
+                let log_msg = format!("failed to load config for {:?}: {}", path, e);
+                self.client
+                    .log_message(MessageType::ERROR, log_msg.clone())
+                    .await;
+                event!(Level::ERROR, "{}", log_msg);
+                Config::default()
+            }
+        }
+    }
+    async fn get_library(&self, config: &Config) -> Arc<Library> {
+        let ws_ref = Arc::clone(&self.workspace);
+        match ws_ref.write() {
+            Ok(mut writer) => writer.library_for(config),
+            Err(_) => Arc::new(Library::default()),
+        }
+    }
+    async fn update_module(
+        &self,
+        path: &Path,
+        content: &str,
+        config: &Config,
+        resolver: &Resolver,
+    ) -> Arc<ModuleGraph> {
+        let ws_ref = Arc::clone(&self.workspace);
+        match ws_ref.write() {
+            Ok(mut writer) => {
+                writer.update_module(path, content, config, resolver);
+                writer.modules()
+            }
+            Err(_) => Arc::default(),
         }
     }
-    async fn set_doc(&self, path: PathBuf, content: String) {
+    async fn index_library(&self, config: &Config) {
+        let resolver = Resolver::new(config, self.get_folders().await);
         let ws_ref = Arc::clone(&self.workspace);
         if let Ok(mut writer) = ws_ref.write() {
-            writer.insert(path, content);
+            writer.index_library(config, &resolver);
         }
     }
-    async fn get_doc(&self, path: PathBuf) -> Option<String> {
+    async fn get_modules(&self) -> Arc<ModuleGraph> {
         let ws_ref = Arc::clone(&self.workspace);
-        if let Ok(reader) = ws_ref.read() {
-            reader.get(&path).cloned()
+        match ws_ref.read() {
+            Ok(reader) => reader.modules(),
+            Err(_) => Arc::default(),
+        }
+    }
+    /// The config and cross-file context used to answer requests about the
+    /// document at `path`.
+    async fn context_for(&self, path: Option<&Path>) -> (Config, Context) {
+        let config = match path {
+            Some(path) => self.get_config(path).await,
+            None => Config::default(),
+        };
+        let context = Context {
+            library: self.get_library(&config).await,
+            resolver: Resolver::new(&config, self.get_folders().await),
+            modules: self.get_modules().await,
+        };
+        (config, context)
+    }
+    fn to_symbol_kind(kind: OutlineKind) -> SymbolKind {
+        match kind {
+            OutlineKind::Function => SymbolKind::FUNCTION,
+            OutlineKind::Method => SymbolKind::METHOD,
+            OutlineKind::Table => SymbolKind::OBJECT,
+            OutlineKind::Module => SymbolKind::MODULE,
+            OutlineKind::Field => SymbolKind::FIELD,
+        }
+    }
+    fn to_document_symbol(
+        item: OutlineItem,
+        line_index: &LineIndex,
+        encoding: PositionEncoding,
+    ) -> DocumentSymbol {
+        let range = |loc: &Location| line_index.range(loc.byte_start, loc.byte_end, encoding);
+        #[allow(deprecated)]
+        DocumentSymbol {
+            kind: Self::to_symbol_kind(item.kind),
+            range: range(&item.range),
+            selection_range: range(&item.selection),
+            children: Some(
+                item.children
+                    .into_iter()
+                    .map(|child| Self::to_document_symbol(child, line_index, encoding))
+                    .collect(),
+            ),
+            name: item.name,
+            detail: item.detail,
+            tags: None,
+            deprecated: None,
+        }
+    }
+    fn to_lsp_location(
+        &self,
+        uri: &Url,
+        line_index: &LineIndex,
+        encoding: PositionEncoding,
+        location: &FileLocation,
+    ) -> Option<lsp_types::Location> {
+        let loc = &location.loc;
+        let Some(path) = &location.path else {
+            return Some(lsp_types::Location {
+                uri: uri.clone(),
+                range: line_index.range(loc.byte_start, loc.byte_end, encoding),
+            });
+        };
+        let uri = Url::from_file_path(path).ok()?;
+        let text = self
+            .get_doc_text(&uri)
+            .or_else(|| fs::read_to_string(path).ok())?;
+        Some(lsp_types::Location {
+            range: LineIndex::new(&text).range(loc.byte_start, loc.byte_end, encoding),
+            uri,
+        })
+    }
+    /// Runs a navigation `query` at `params` and converts its results.
+    async fn navigate(
+        &self,
+        params: &TextDocumentPositionParams,
+        query: impl FnOnce(&Analysis, usize, Option<&Path>, &Context) -> Vec<FileLocation>,
+    ) -> Option<GotoDefinitionResponse> {
+        let uri = &params.text_document.uri;
+        let text = self.get_doc_text(uri)?;
+        let path = uri.to_file_path().ok();
+        let (config, context) = self.context_for(path.as_deref()).await;
+        let encoding = self.get_position_encoding().await;
+        let line_index = LineIndex::new(&text);
+        let offset = line_index.offset(params.position, encoding)?;
+        let analysis = Analysis::new(&text, config.runtime.version);
+        let mut locations: Vec<lsp_types::Location> =
+            query(&analysis, offset, path.as_deref(), &context)
+                .iter()
+                .filter_map(|location| self.to_lsp_location(uri, &line_index, encoding, location))
+                .collect();
+        match locations.len() {
+            0 => None,
+            1 => Some(GotoDefinitionResponse::Scalar(locations.remove(0))),
+            _ => Some(GotoDefinitionResponse::Array(locations)),
+        }
+    }
+    /// Occurrences of the name at `params`, grouped by document. With
+    /// `workspace`, globals and module fields are searched in every document
+    /// of the workspace; otherwise only the current one is.
+    async fn find_occurrences(
+        &self,
+        params: &TextDocumentPositionParams,
+        workspace: bool,
+    ) -> Option<(Key, Vec<(Url, LineIndex, Vec<Occurrence>)>)> {
+        let uri = &params.text_document.uri;
+        let text = self.get_doc_text(uri)?;
+        let path = uri.to_file_path().ok();
+        let (config, context) = self.context_for(path.as_deref()).await;
+        let encoding = self.get_position_encoding().await;
+        let line_index = LineIndex::new(&text);
+        let offset = line_index.offset(params.position, encoding)?;
+        let analysis = Analysis::new(&text, config.runtime.version);
+        let key = analysis.key_at(offset, path.as_deref(), &context)?;
+        let occurrences = analysis.occurrences(&key, path.as_deref(), &context);
+        let name = key.name(&analysis.tree).to_string();
+        let mut ret = vec![(uri.clone(), line_index, occurrences)];
+        if !workspace || key.is_file_local() {
+            return Some((key, ret));
+        }
+        let mut docs: Vec<(Url, String)> = {
+            let ws_ref = Arc::clone(&self.workspace);
+            match ws_ref.read() {
+                Ok(reader) => reader
+                    .documents()
+                    .filter(|(doc_uri, _)| *doc_uri != uri)
+                    .map(|(uri, doc)| (uri.clone(), doc.text()))
+                    .filter(|(_, text)| text.contains(&name))
+                    .collect(),
+                Err(_) => Vec::new(),
+            }
+        };
+        docs.sort_by(|a, b| a.0.cmp(&b.0));
+        for (uri, text) in docs {
+            let path = uri.to_file_path().ok();
+            let (config, context) = self.context_for(path.as_deref()).await;
+            let occurrences = Analysis::new(&text, config.runtime.version).occurrences(
+                &key,
+                path.as_deref(),
+                &context,
+            );
+            if !occurrences.is_empty() {
+                ret.push((uri, LineIndex::new(&text), occurrences));
+            }
+        }
+        Some((key, ret))
+    }
+    async fn invalidate_config(&self, path: &Path) {
+        let ws_ref = Arc::clone(&self.workspace);
+        if let Ok(mut writer) = ws_ref.write() {
+            writer.invalidate_config(path);
+        }
+        let log_msg = format!("reload config {:?}", path);
+        self.client
+            .log_message(MessageType::INFO, log_msg.clone())
+            .await;
+        event!(Level::INFO, "{}", log_msg);
+    }
+    async fn index_folder(&self, folder: &Path) {
+        let mut files = match workspace::lua_files(folder) {
+            Ok(files) => files,
+            Err(e) => {
+                event!(Level::INFO, "failed to index {:?}: {}", folder, e);
+                return;
+            }
+        };
+        let config = self.get_config(folder).await;
+        match workspace::resolve_paths(config.base_dir(), &config.runtime.include) {
+            Ok(included) => files.extend(included),
+            Err(e) => event!(Level::INFO, "failed to resolve runtime.include: {}", e),
+        }
+        // Every module is indexed before any is checked so that cross-file
+        // diagnostics see the whole folder.
+        let mut indexed = Vec::new();
+        for p in files {
+            event!(Level::INFO, "read {:?} in workspace", &p);
+            let content = match fs::read_to_string(&p) {
+                Ok(content) => content,
+                Err(e) => {
+                    event!(Level::INFO, "failed to read {:?}: {}", &p, e);
+                    continue;
+                }
+            };
+            if let Ok(uri) = Url::from_file_path(&p) {
+                self.index_doc(uri.clone(), &content).await;
+                let config = self.get_config(&p).await;
+                let resolver = Resolver::new(&config, self.get_folders().await);
+                self.update_module(&p, &content, &config, &resolver).await;
+                indexed.push(uri);
+            }
+        }
+        self.index_library(&config).await;
+        self.mark_indexed(folder).await;
+        for uri in indexed {
+            if let Some(content) = self.get_doc_text(&uri) {
+                self.check_syntax(uri, content).await;
+            }
+        }
+    }
+    async fn recheck_workspace(&self) {
+        let docs: Vec<(Url, String)> = {
+            let ws_ref = Arc::clone(&self.workspace);
+            match ws_ref.read() {
+                Ok(reader) => reader
+                    .documents()
+                    .map(|(uri, doc)| (uri.clone(), doc.text()))
+                    .collect(),
+                Err(_) => Vec::new(),
+            }
+        };
+        for (uri, content) in docs {
+            self.check_syntax(uri, content).await;
+        }
+    }
+    async fn set_position_encoding(&self, encoding: PositionEncoding) {
+        let enc_ref = Arc::clone(&self.position_encoding);
+        if let Ok(mut writer) = enc_ref.write() {
+            *writer = encoding;
+        }
+    }
+    async fn get_position_encoding(&self) -> PositionEncoding {
+        let enc_ref = Arc::clone(&self.position_encoding);
+        if let Ok(reader) = enc_ref.read() {
+            *reader
         } else {
-            None
+            PositionEncoding::default()
         }
     }
+    async fn index_doc(&self, uri: Url, content: &str) {
+        let ws_ref = Arc::clone(&self.workspace);
+        if let Ok(mut writer) = ws_ref.write() {
+            writer.index_doc(uri, content);
+        }
+    }
+    async fn mark_indexed(&self, folder: &Path) {
+        let ws_ref = Arc::clone(&self.workspace);
+        if let Ok(mut writer) = ws_ref.write() {
+            writer.mark_indexed(folder);
+        }
+    }
+    async fn open_doc(&self, uri: Url, content: &str, version: i32) {
+        let ws_ref = Arc::clone(&self.workspace);
+        if let Ok(mut writer) = ws_ref.write() {
+            writer.open_doc(uri, content, version);
+        }
+    }
+    async fn close_doc(&self, uri: &Url) -> Option<String> {
+        let ws_ref = Arc::clone(&self.workspace);
+        let mut writer = ws_ref.write().ok()?;
+        writer.close_doc(uri)
+    }
+    async fn change_doc(
+        &self,
+        uri: &Url,
+        version: i32,
+        changes: &[TextDocumentContentChangeEvent],
+    ) -> Result<String> {
+        let encoding = self.get_position_encoding().await;
+        let ws_ref = Arc::clone(&self.workspace);
+        let mut writer = ws_ref
+            .write()
+            .map_err(|_| anyhow!("failed to lock workspace"))?;
+        writer.change_doc(uri, version, changes, encoding)
+    }
 }
 
 #[tower_lsp::async_trait]
@@ -125,7 +578,7 @@ impl LanguageServer for Backend {
     async fn initialize(&self, params: InitializeParams) -> LspResult<InitializeResult> {
         let text_document_sync = TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
             open_close: Some(true),
-            change: Some(TextDocumentSyncKind::FULL),
+            change: Some(TextDocumentSyncKind::INCREMENTAL),
             will_save: Some(false),
             will_save_wait_until: Some(false),
             save: None,
@@ -147,36 +600,27 @@ impl LanguageServer for Backend {
             },
             None => None,
         };
+        self.set_position_encoding(
+            position_encoding
+                .as_ref()
+                .and_then(PositionEncoding::from_kind)
+                .unwrap_or_default(),
+        )
+        .await;
         let server_info = Some(ServerInfo {
             name: "luascan".to_string(),
             version: Some(VERSION.to_string()),
         });
-        if let Some(url) = params.root_uri.clone() {
-            let mut path = PathBuf::from(url.path());
-            let _ = self.set_root(path.clone()).await;
-            // path.push("**/*.lua");
-            // for entry in glob(path.to_str().expect("failed to convert from path to str"))
-            //     .expect("failed to read path")
-            // {
-            //     match entry {
-            //         Ok(p) => {
-            //             event!(Level::INFO, "read {:?} in workspace", &p);
-            //             let mut content = String::new();
-            //             let mut file = File::open(&p).expect("failed to open file");
-            //             file.read_to_string(&mut content)
-            //                 .expect("failed to read file");
-            //             self.set_doc(p.clone(), content.clone()).await;
-            //             let uri = Url::from_file_path(
-            //                 p.to_str().expect("failed to convert from path to str"),
-            //             )
-            //             .expect("failed to parse url");
-            //             self.check_syntax(uri, content).await;
-            //         }
-            //         Err(e) => {
-            //             event!(Level::INFO, "glob error {:?}", e);
-            //         }
-            //     }
-            // }
+        let folders: Vec<Url> = match params.workspace_folders {
+            Some(folders) if !folders.is_empty() => folders.into_iter().map(|f| f.uri).collect(),
+            _ => params.root_uri.into_iter().collect(),
+        };
+        for url in folders {
+            if let Ok(path) = url.to_file_path()
+                && let Err(e) = self.add_folder(path).await
+            {
+                event!(Level::INFO, "{}", e);
+            }
         }
         Ok(InitializeResult {
             server_info,
@@ -190,41 +634,56 @@ impl LanguageServer for Backend {
                     }),
                     file_operations: None,
                 }),
+                definition_provider: Some(OneOf::Left(true)),
+                declaration_provider: Some(DeclarationCapability::Simple(true)),
+                type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                })),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(["(", ","].map(str::to_string).to_vec()),
+                    ..SignatureHelpOptions::default()
+                }),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some([".", ":", "\"", "'"].map(str::to_string).to_vec()),
+                    ..CompletionOptions::default()
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        ..CodeActionOptions::default()
+                    },
+                )),
                 ..ServerCapabilities::default()
             },
         })
     }
 
     async fn initialized(&self, _: InitializedParams) {
-        let mut root_path = self.get_root().await.expect("failed to get root path");
-        root_path.push("**/*.lua");
-        for entry in glob(
-            root_path
-                .to_str()
-                .expect("failed to convert from path to str"),
-        )
-        .expect("failed to read path")
-        {
-            match entry {
-                Ok(p) => {
-                    event!(Level::INFO, "read {:?} in workspace", &p);
-                    let mut content = String::new();
-                    let mut file = File::open(&p).expect("failed to open file");
-                    file.read_to_string(&mut content)
-                        .expect("failed to read file");
-                    self.set_doc(p.clone(), content.clone()).await;
-                    let uri = Url::from_file_path(
-                        p.to_str().expect("failed to convert from path to str"),
-                    )
-                    .expect("failed to parse url");
-                    self.check_syntax(uri, content).await;
-                }
-                Err(e) => {
-                    event!(Level::INFO, "glob error {:?}", e);
-                }
-            }
+        let registration = Registration {
+            id: "luascan-watch-config".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(format!("**/{DEFAULT_CONFIG_FILENAME}")),
+                    kind: None,
+                }],
+            })
+            .ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            event!(Level::INFO, "failed to watch config files {:?}", e);
+        }
+        for folder in self.get_folders().await {
+            self.index_folder(&folder).await;
         }
-        let log_msg = format!("initialized in {:?}", self.get_root().await);
+        let log_msg = format!("initialized in {:?}", self.get_folders().await);
         self.client
             .log_message(MessageType::INFO, log_msg.clone())
             .await;
@@ -232,7 +691,7 @@ impl LanguageServer for Backend {
     }
 
     async fn shutdown(&self) -> LspResult<()> {
-        let log_msg = format!("shutdown in {:?}", self.get_root().await);
+        let log_msg = format!("shutdown in {:?}", self.get_folders().await);
         self.client
             .log_message(MessageType::INFO, log_msg.clone())
             .await;
@@ -240,7 +699,7 @@ impl LanguageServer for Backend {
         Ok(())
     }
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        let log_msg = format!("did open in {:?}", self.get_root().await);
+        let log_msg = format!("did open in {:?}", self.get_folders().await);
         self.client
             .log_message(MessageType::INFO, log_msg.clone())
             .await;
@@ -251,27 +710,464 @@ impl LanguageServer for Backend {
         {
             let uri = params.text_document.uri;
             let content = params.text_document.text;
-            self.set_doc(PathBuf::from(uri.path()), content.clone())
+            self.open_doc(uri.clone(), &content, params.text_document.version)
                 .await;
-            self.check_syntax(uri, content).await;
+            self.check_with_related(uri, content).await;
         }
     }
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        let log_msg = format!("did change in {:?}", self.get_root().await);
+        let log_msg = format!("did change in {:?}", self.get_folders().await);
         self.client
             .log_message(MessageType::INFO, log_msg.clone())
             .await;
         event!(Level::INFO, "{}", log_msg);
         let uri = params.text_document.uri;
-        let content = params.content_changes[0].text.clone();
-        if let Ok(path) = uri.to_file_path()
-            && path.is_file()
+        let version = params.text_document.version;
+        match self
+            .change_doc(&uri, version, &params.content_changes)
+            .await
         {
-            self.check_syntax(uri, content.clone()).await;
+            Ok(content) => self.check_with_related(uri, content).await,
+            Err(e) => {
+                let log_msg = format!("failed to apply change: {}", e);
+                self.client
+                    .log_message(MessageType::WARNING, log_msg.clone())
+                    .await;
+                event!(Level::WARN, "{}", log_msg);
+            }
+        }
+    }
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        match self.close_doc(&uri).await {
+            Some(content) => self.check_with_related(uri, content).await,
+            None => {
+                self.client
+                    .publish_diagnostics(uri.clone(), Vec::new(), None)
+                    .await;
+                if let Ok(path) = uri.to_file_path() {
+                    self.check_related(&path).await;
+                }
+            }
+        }
+    }
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        for folder in params.event.removed {
+            let Ok(path) = folder.uri.to_file_path() else {
+                continue;
+            };
+            for uri in self.remove_folder(&path).await {
+                self.client.publish_diagnostics(uri, Vec::new(), None).await;
+            }
+        }
+        for folder in params.event.added {
+            let Ok(path) = folder.uri.to_file_path() else {
+                continue;
+            };
+            match self.add_folder(path.clone()).await {
+                Ok(()) => self.index_folder(&path).await,
+                Err(e) => event!(Level::INFO, "{}", e),
+            }
+        }
+        let log_msg = format!("workspace folders {:?}", self.get_folders().await);
+        self.client
+            .log_message(MessageType::INFO, log_msg.clone())
+            .await;
+        event!(Level::INFO, "{}", log_msg);
+    }
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let mut reloaded = false;
+        for change in params.changes {
+            let Ok(path) = change.uri.to_file_path() else {
+                continue;
+            };
+            if !Config::is_config_path(&path) {
+                continue;
+            }
+            event!(Level::INFO, "config {:?} changed: {:?}", &path, change.typ);
+            self.invalidate_config(&path).await;
+            reloaded = true;
+        }
+        if reloaded {
+            self.recheck_workspace().await;
+        }
+    }
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> LspResult<Option<GotoDefinitionResponse>> {
+        Ok(self
+            .navigate(
+                &params.text_document_position_params,
+                |analysis, offset, path, context| analysis.definition(offset, path, context),
+            )
+            .await)
+    }
+    /// Lua has no separate declarations: a local is declared and defined by
+    /// its `local` statement and a global by its first assignment.
+    async fn goto_declaration(
+        &self,
+        params: GotoDeclarationParams,
+    ) -> LspResult<Option<GotoDeclarationResponse>> {
+        self.goto_definition(params).await
+    }
+    async fn goto_type_definition(
+        &self,
+        params: GotoTypeDefinitionParams,
+    ) -> LspResult<Option<GotoTypeDefinitionResponse>> {
+        Ok(self
+            .navigate(
+                &params.text_document_position_params,
+                |analysis, offset, _, context| {
+                    analysis
+                        .type_definition(offset, context)
+                        .into_iter()
+                        .collect()
+                },
+            )
+            .await)
+    }
+    async fn references(
+        &self,
+        params: ReferenceParams,
+    ) -> LspResult<Option<Vec<lsp_types::Location>>> {
+        let include_declaration = params.context.include_declaration;
+        let Some((_, found)) = self
+            .find_occurrences(&params.text_document_position, true)
+            .await
+        else {
+            return Ok(None);
+        };
+        let encoding = self.get_position_encoding().await;
+        let locations = found
+            .iter()
+            .flat_map(|(uri, line_index, occurrences)| {
+                occurrences
+                    .iter()
+                    .filter(|o| include_declaration || !o.declaration)
+                    .map(move |o| lsp_types::Location {
+                        uri: uri.clone(),
+                        range: line_index.range(o.loc.byte_start, o.loc.byte_end, encoding),
+                    })
+            })
+            .collect();
+        Ok(Some(locations))
+    }
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> LspResult<Option<Vec<DocumentHighlight>>> {
+        let Some((_, found)) = self
+            .find_occurrences(&params.text_document_position_params, false)
+            .await
+        else {
+            return Ok(None);
+        };
+        let encoding = self.get_position_encoding().await;
+        let highlights = found
+            .iter()
+            .flat_map(|(_, line_index, occurrences)| {
+                occurrences.iter().map(move |o| DocumentHighlight {
+                    range: line_index.range(o.loc.byte_start, o.loc.byte_end, encoding),
+                    kind: Some(match o.access {
+                        Access::Read => DocumentHighlightKind::READ,
+                        Access::Write => DocumentHighlightKind::WRITE,
+                    }),
+                })
+            })
+            .collect();
+        Ok(Some(highlights))
+    }
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> LspResult<Option<PrepareRenameResponse>> {
+        let uri = &params.text_document.uri;
+        let Some(text) = self.get_doc_text(uri) else {
+            return Ok(None);
+        };
+        let path = uri.to_file_path().ok();
+        let (config, context) = self.context_for(path.as_deref()).await;
+        let encoding = self.get_position_encoding().await;
+        let line_index = LineIndex::new(&text);
+        let Some(offset) = line_index.offset(params.position, encoding) else {
+            return Ok(None);
+        };
+        let analysis = Analysis::new(&text, config.runtime.version);
+        let Some(key) = analysis.key_at(offset, path.as_deref(), &context) else {
+            return Ok(None);
+        };
+        analysis
+            .check_renamable(&key, &context)
+            .map_err(|e| jsonrpc::Error::invalid_params(e.to_string()))?;
+        Ok(analysis
+            .occurrences(&key, path.as_deref(), &context)
+            .iter()
+            .find(|o| o.loc.byte_start <= offset && offset <= o.loc.byte_end)
+            .map(|o| {
+                PrepareRenameResponse::Range(line_index.range(
+                    o.loc.byte_start,
+                    o.loc.byte_end,
+                    encoding,
+                ))
+            }))
+    }
+    async fn rename(&self, params: RenameParams) -> LspResult<Option<WorkspaceEdit>> {
+        let new_name = params.new_name;
+        let invalid = |e: anyhow::Error| jsonrpc::Error::invalid_params(e.to_string());
+        analysis::validate_name(&new_name).map_err(invalid)?;
+        let Some((key, found)) = self
+            .find_occurrences(&params.text_document_position, true)
+            .await
+        else {
+            return Ok(None);
+        };
+        let encoding = self.get_position_encoding().await;
+        let mut changes = HashMap::new();
+        for (i, (uri, line_index, occurrences)) in found.iter().enumerate() {
+            let Some(text) = self.get_doc_text(uri) else {
+                continue;
+            };
+            let path = uri.to_file_path().ok();
+            let (config, context) = self.context_for(path.as_deref()).await;
+            let analysis = Analysis::new(&text, config.runtime.version);
+            if i == 0 {
+                analysis.check_renamable(&key, &context).map_err(invalid)?;
+            }
+            analysis
+                .check_rename(&key, occurrences, &new_name, path.as_deref(), &context)
+                .map_err(invalid)?;
+            let edits: Vec<TextEdit> = occurrences
+                .iter()
+                .map(|o| TextEdit {
+                    range: line_index.range(o.loc.byte_start, o.loc.byte_end, encoding),
+                    new_text: new_name.clone(),
+                })
+                .collect();
+            changes.insert(uri.clone(), edits);
+        }
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+        }))
+    }
+    async fn hover(&self, params: HoverParams) -> LspResult<Option<Hover>> {
+        let params = params.text_document_position_params;
+        let uri = &params.text_document.uri;
+        let Some(text) = self.get_doc_text(uri) else {
+            return Ok(None);
+        };
+        let path = uri.to_file_path().ok();
+        let (config, context) = self.context_for(path.as_deref()).await;
+        let encoding = self.get_position_encoding().await;
+        let line_index = LineIndex::new(&text);
+        let Some(offset) = line_index.offset(params.position, encoding) else {
+            return Ok(None);
+        };
+        let analysis = Analysis::new(&text, config.runtime.version);
+        Ok(analysis
+            .hover(offset, path.as_deref(), &context)
+            .map(|(loc, markdown)| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: markdown,
+                }),
+                range: Some(line_index.range(loc.byte_start, loc.byte_end, encoding)),
+            }))
+    }
+    async fn completion(&self, params: CompletionParams) -> LspResult<Option<CompletionResponse>> {
+        let params = params.text_document_position;
+        let uri = &params.text_document.uri;
+        let Some(text) = self.get_doc_text(uri) else {
+            return Ok(None);
+        };
+        let path = uri.to_file_path().ok();
+        let (config, context) = self.context_for(path.as_deref()).await;
+        let encoding = self.get_position_encoding().await;
+        let line_index = LineIndex::new(&text);
+        let Some(offset) = line_index.offset(params.position, encoding) else {
+            return Ok(None);
+        };
+        let analysis = Analysis::new(&text, config.runtime.version);
+        let completions = completion::complete(&analysis, offset, path.as_deref(), &context);
+        let range = line_index.range(completions.start, offset, encoding);
+        let items = completions
+            .items
+            .into_iter()
+            .map(|item| CompletionItem {
+                kind: Some(match item.kind {
+                    CompletionKind::Variable => CompletionItemKind::VARIABLE,
+                    CompletionKind::Function => CompletionItemKind::FUNCTION,
+                    CompletionKind::Field => CompletionItemKind::FIELD,
+                    CompletionKind::Module => CompletionItemKind::MODULE,
+                    CompletionKind::Keyword => CompletionItemKind::KEYWORD,
+                    CompletionKind::Snippet => CompletionItemKind::SNIPPET,
+                }),
+                detail: item.detail,
+                documentation: item.documentation.map(|value| {
+                    Documentation::MarkupContent(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value,
+                    })
+                }),
+                insert_text_format: item.snippet.map(|_| InsertTextFormat::SNIPPET),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range,
+                    new_text: item
+                        .snippet
+                        .map_or_else(|| item.label.clone(), str::to_string),
+                })),
+                label: item.label,
+                ..CompletionItem::default()
+            })
+            .collect();
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+    async fn signature_help(
+        &self,
+        params: SignatureHelpParams,
+    ) -> LspResult<Option<SignatureHelp>> {
+        let params = params.text_document_position_params;
+        let uri = &params.text_document.uri;
+        let Some(text) = self.get_doc_text(uri) else {
+            return Ok(None);
+        };
+        let path = uri.to_file_path().ok();
+        let (config, context) = self.context_for(path.as_deref()).await;
+        let encoding = self.get_position_encoding().await;
+        let line_index = LineIndex::new(&text);
+        let Some(offset) = line_index.offset(params.position, encoding) else {
+            return Ok(None);
+        };
+        let analysis = Analysis::new(&text, config.runtime.version);
+        Ok(
+            signature::signature_at(&analysis, offset, path.as_deref(), &context).map(
+                |signature| {
+                    let active = u32::try_from(signature.active).ok();
+                    SignatureHelp {
+                        signatures: vec![SignatureInformation {
+                            label: signature.label,
+                            documentation: signature.documentation.map(|value| {
+                                Documentation::MarkupContent(MarkupContent {
+                                    kind: MarkupKind::Markdown,
+                                    value,
+                                })
+                            }),
+                            parameters: Some(
+                                signature
+                                    .params
+                                    .into_iter()
+                                    .map(|param| ParameterInformation {
+                                        label: ParameterLabel::Simple(param),
+                                        documentation: None,
+                                    })
+                                    .collect(),
+                            ),
+                            active_parameter: active,
+                        }],
+                        active_signature: Some(0),
+                        active_parameter: active,
+                    }
+                },
+            ),
+        )
+    }
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> LspResult<Option<DocumentSymbolResponse>> {
+        let uri = &params.text_document.uri;
+        let Some(text) = self.get_doc_text(uri) else {
+            return Ok(None);
+        };
+        let config = match uri.to_file_path() {
+            Ok(path) => self.get_config(&path).await,
+            Err(_) => Config::default(),
+        };
+        let encoding = self.get_position_encoding().await;
+        let line_index = LineIndex::new(&text);
+        let symbols = outline::outline(&text, config.runtime.version)
+            .into_iter()
+            .map(|item| Self::to_document_symbol(item, &line_index, encoding))
+            .collect();
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> LspResult<Option<Vec<SymbolInformation>>> {
+        let found: Vec<(PathBuf, IndexedSymbol)> = {
+            let ws_ref = Arc::clone(&self.workspace);
+            match ws_ref.read() {
+                Ok(reader) => reader
+                    .symbols()
+                    .search(&params.query)
+                    .into_iter()
+                    .map(|(path, symbol)| (path.to_path_buf(), symbol.clone()))
+                    .collect(),
+                Err(_) => return Ok(None),
+            }
+        };
+        let encoding = self.get_position_encoding().await;
+        let mut line_indexes: HashMap<PathBuf, Option<LineIndex>> = HashMap::new();
+        let mut symbols = Vec::new();
+        for (path, symbol) in found {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            let line_index = line_indexes.entry(path.clone()).or_insert_with(|| {
+                self.get_doc_text(&uri)
+                    .or_else(|| fs::read_to_string(&path).ok())
+                    .map(|text| LineIndex::new(&text))
+            });
+            let Some(line_index) = line_index else {
+                continue;
+            };
+            let loc = &symbol.loc;
+            #[allow(deprecated)]
+            symbols.push(SymbolInformation {
+                name: symbol.name,
+                kind: Self::to_symbol_kind(symbol.kind),
+                tags: None,
+                deprecated: None,
+                location: lsp_types::Location {
+                    uri,
+                    range: line_index.range(loc.byte_start, loc.byte_end, encoding),
+                },
+                container_name: symbol.container,
+            });
         }
+        Ok(Some(symbols))
+    }
+    async fn code_action(&self, params: CodeActionParams) -> LspResult<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let mut actions = Vec::new();
+        for diagnostic in params.context.diagnostics {
+            let Some(data) = diagnostic.data.clone() else {
+                continue;
+            };
+            let Ok(fixes) = serde_json::from_value::<Vec<QuickFix>>(data) else {
+                continue;
+            };
+            let preferred = fixes.len() == 1;
+            for fix in fixes {
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri.clone(), vec![fix.edit])])),
+                        ..WorkspaceEdit::default()
+                    }),
+                    is_preferred: Some(preferred),
+                    ..CodeAction::default()
+                }));
+            }
+        }
+        Ok(Some(actions))
     }
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
-        let log_msg = format!("did save in {:?}", self.get_root().await);
+        let log_msg = format!("did save in {:?}", self.get_folders().await);
         self.client
             .log_message(MessageType::INFO, log_msg.clone())
             .await;
@@ -281,7 +1177,7 @@ impl LanguageServer for Backend {
             && path.is_file()
             && let Some(content) = params.text
         {
-            self.check_syntax(uri, content.clone()).await;
+            self.check_with_related(uri, content.clone()).await;
         }
     }
 }
@@ -305,11 +1201,14 @@ mod tests {
     use super::*;
     use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, duplex};
     use tower_lsp::LspService;
+    use tower_lsp::lsp_types::{FileChangeType, FileEvent};
 
     use crate::{
         cli::LspOptions,
-        config::{Config, RuntimeConfig, WorkspaceConfig},
+        config::{Config, RulesConfig, RuntimeConfig, RuntimeVersion, WorkspaceConfig},
+        diagnostic::RuleCode,
         lsp::Backend,
+        parser,
     };
 
     fn req(msg: &str) -> String {
@@ -332,6 +1231,8 @@ mod tests {
                 workspace: WorkspaceConfig {
                     library: Vec::new(),
                 },
+                rules: RulesConfig::default(),
+                root: None,
             },
         };
         let (mut req_client, mut resp_client) = create_lsp(options);
@@ -353,4 +1254,434 @@ mod tests {
         let _ = resp_client.read(&mut buf).await.unwrap();
         assert!(!buf.is_empty())
     }
+
+    #[tokio::test]
+    async fn test_config_for_document() {
+        let service = service();
+        let backend = service.inner();
+        let config = backend
+            .get_config(Path::new("tests/multi_root/lua54/main.lua"))
+            .await;
+        assert!(matches!(config.runtime.version, RuntimeVersion::Lua54));
+        let config = backend
+            .get_config(Path::new("tests/sample_workspace/init.lua"))
+            .await;
+        assert!(matches!(config.runtime.version, RuntimeVersion::Luajit));
+    }
+
+    #[tokio::test]
+    async fn test_reload_config() {
+        let service = service();
+        let backend = service.inner();
+        let dir = std::env::temp_dir().join(format!("luascan-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = Config::config_path(&dir);
+        fs::write(&config_path, "[runtime]\nversion = \"lua51\"\n").unwrap();
+        let main = dir.join("main.lua");
+        fs::write(&main, "print(1)\n").unwrap();
+        backend
+            .open_doc(Url::from_file_path(&main).unwrap(), "print(1)\n", 1)
+            .await;
+        let version = |config: Config| config.runtime.version;
+        assert_eq!(
+            version(backend.get_config(&main).await),
+            RuntimeVersion::Lua51
+        );
+
+        fs::write(&config_path, "[runtime]\nversion = \"lua54\"\n").unwrap();
+        // The cached config stays until the client reports the change.
+        assert_eq!(
+            version(backend.get_config(&main).await),
+            RuntimeVersion::Lua51
+        );
+        backend
+            .did_change_watched_files(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent {
+                    uri: Url::from_file_path(&config_path).unwrap(),
+                    typ: FileChangeType::CHANGED,
+                }],
+            })
+            .await;
+        assert_eq!(
+            version(backend.get_config(&main).await),
+            RuntimeVersion::Lua54
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_to_lsp_diagnostic() {
+        let service = service();
+        let backend = service.inner();
+        let content = "if a then\n  print(a)\n";
+        let uri = Url::parse("file:///a.lua").unwrap();
+        let d = &parser::parse(content, RuntimeVersion::Lua54).1[0];
+        let d = d
+            .clone()
+            .with_related(d.loc.clone(), "block starts here")
+            .with_fix(d.loc.clone(), "end", "insert `end`")
+            .with_tag(Tag::Unnecessary);
+        let line_index = LineIndex::new(content);
+        let diagnostic = backend.to_lsp_diagnostic(&uri, &line_index, PositionEncoding::Utf16, &d);
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::String("E006".to_string()))
+        );
+        assert_eq!(
+            diagnostic.code_description.unwrap().href.as_str(),
+            RuleCode::UnclosedBlock.docs_url()
+        );
+        assert_eq!(diagnostic.source.as_deref(), Some("luascan"));
+        assert_eq!(diagnostic.related_information.unwrap()[0].location.uri, uri);
+        assert_eq!(diagnostic.tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+        let fixes: Vec<QuickFix> = serde_json::from_value(diagnostic.data.unwrap()).unwrap();
+        assert_eq!(fixes[0].edit.new_text, "end");
+    }
+
+    fn service() -> LspService<Backend> {
+        let (service, _) = LspService::new(|client| {
+            Backend::new(
+                client,
+                LspOptions {
+                    config: Config::default(),
+                },
+            )
+        });
+        service
+    }
+
+    /// Opens `name` from the fixture `folder`, added as a workspace folder.
+    async fn open_module(backend: &Backend, folder: &Path, name: &str) -> Url {
+        backend.add_folder(folder.to_path_buf()).await.unwrap();
+        let path = folder.join(name);
+        let uri = Url::from_file_path(&path).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        backend.open_doc(uri.clone(), &content, 1).await;
+        let (config, context) = backend.context_for(Some(&path)).await;
+        backend
+            .update_module(&path, &content, &config, &context.resolver)
+            .await;
+        uri
+    }
+
+    #[tokio::test]
+    async fn test_goto_definition() {
+        let service = service();
+        let backend = service.inner();
+        let folder = fs::canonicalize("tests/modules").unwrap();
+        let uri = open_module(backend, &folder, "main.lua").await;
+        let params = |line, character| GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+                position: lsp_types::Position::new(line, character),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let Some(GotoDefinitionResponse::Scalar(location)) =
+            backend.goto_definition(params(4, 5)).await.unwrap()
+        else {
+            panic!("expected a single definition");
+        };
+        assert_eq!(
+            location.uri,
+            Url::from_file_path(folder.join("ui.lua")).unwrap()
+        );
+        assert_eq!(location.range.start, lsp_types::Position::new(4, 2));
+        let Some(GotoDefinitionResponse::Scalar(location)) =
+            backend.goto_declaration(params(4, 0)).await.unwrap()
+        else {
+            panic!("expected a single declaration");
+        };
+        assert_eq!(location.uri, uri);
+        assert_eq!(location.range.start, lsp_types::Position::new(0, 6));
+        assert!(
+            backend
+                .goto_definition(params(3, 4))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_references_and_highlights() {
+        let service = service();
+        let backend = service.inner();
+        let folder = fs::canonicalize("tests/modules").unwrap();
+        let main = open_module(backend, &folder, "main.lua").await;
+        let ui = open_module(backend, &folder, "ui.lua").await;
+        let position = TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri: main.clone() },
+            position: lsp_types::Position::new(4, 5),
+        };
+        let references = backend
+            .references(ReferenceParams {
+                text_document_position: position.clone(),
+                context: lsp_types::ReferenceContext {
+                    include_declaration: true,
+                },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let found: Vec<(Url, u32)> = references
+            .into_iter()
+            .map(|l| (l.uri, l.range.start.line))
+            .collect();
+        assert_eq!(found, vec![(main.clone(), 4), (ui, 4)]);
+
+        let highlights = backend
+            .document_highlight(DocumentHighlightParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    position: lsp_types::Position::new(0, 7),
+                    ..position
+                },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let kinds: Vec<(u32, Option<DocumentHighlightKind>)> = highlights
+            .into_iter()
+            .map(|h| (h.range.start.line, h.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (0, Some(DocumentHighlightKind::WRITE)),
+                (3, Some(DocumentHighlightKind::READ)),
+                (4, Some(DocumentHighlightKind::READ)),
+                (4, Some(DocumentHighlightKind::READ)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_completion() {
+        let service = service();
+        let backend = service.inner();
+        let folder = fs::canonicalize("tests/modules").unwrap();
+        let main = open_module(backend, &folder, "main.lua").await;
+        let content = fs::read_to_string(folder.join("main.lua")).unwrap() + "ui.re";
+        backend.open_doc(main.clone(), &content, 2).await;
+        let Some(CompletionResponse::Array(items)) = backend
+            .completion(CompletionParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri: main },
+                    position: lsp_types::Position::new(6, 5),
+                },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: None,
+            })
+            .await
+            .unwrap()
+        else {
+            panic!("expected completions");
+        };
+        let render = items.iter().find(|item| item.label == "render").unwrap();
+        assert_eq!(render.kind, Some(CompletionItemKind::FUNCTION));
+        assert_eq!(render.detail.as_deref(), Some("function(theme)"));
+        let Some(CompletionTextEdit::Edit(edit)) = &render.text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(
+            (edit.range.start, edit.range.end),
+            (
+                lsp_types::Position::new(6, 3),
+                lsp_types::Position::new(6, 5)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signature_help() {
+        let service = service();
+        let backend = service.inner();
+        let folder = fs::canonicalize("tests/sample_workspace").unwrap();
+        let uri = open_module(backend, &folder, "init.lua").await;
+        let content = "local utils = require(\"ui.utils\")\nutils.sample_util_func(1, ";
+        backend.open_doc(uri.clone(), content, 2).await;
+        let path = folder.join("init.lua");
+        let (config, context) = backend.context_for(Some(&path)).await;
+        backend
+            .update_module(&path, content, &config, &context.resolver)
+            .await;
+        let help = backend
+            .signature_help(SignatureHelpParams {
+                context: None,
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri },
+                    position: lsp_types::Position::new(1, 26),
+                },
+                work_done_progress_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(help.signatures[0].label, "utils.sample_util_func(x, y)");
+        assert_eq!(help.active_parameter, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_document_symbol() {
+        let service = service();
+        let backend = service.inner();
+        let folder = fs::canonicalize("tests/modules").unwrap();
+        let ui = open_module(backend, &folder, "ui.lua").await;
+        let Some(DocumentSymbolResponse::Nested(symbols)) = backend
+            .document_symbol(DocumentSymbolParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri: ui },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+        else {
+            panic!("expected nested symbols");
+        };
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(
+            (symbols[0].name.as_str(), symbols[0].kind),
+            ("M", SymbolKind::MODULE)
+        );
+        let children: Vec<(&str, SymbolKind)> = symbols[0]
+            .children
+            .iter()
+            .flatten()
+            .map(|child| (child.name.as_str(), child.kind))
+            .collect();
+        assert_eq!(
+            children,
+            vec![
+                ("theme", SymbolKind::FIELD),
+                ("initialize_ui", SymbolKind::FUNCTION),
+                ("render", SymbolKind::FUNCTION),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_workspace_symbol() {
+        let service = service();
+        let backend = service.inner();
+        let folder = fs::canonicalize("tests/modules").unwrap();
+        let ui = open_module(backend, &folder, "ui.lua").await;
+        open_module(backend, &folder, "main.lua").await;
+        let symbols = backend
+            .symbol(WorkspaceSymbolParams {
+                query: "initui".to_string(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let symbol = &symbols[0];
+        assert_eq!(
+            (symbol.name.as_str(), symbol.kind),
+            ("ui.initialize_ui", SymbolKind::FUNCTION)
+        );
+        assert_eq!(symbol.location.uri, ui);
+        assert_eq!(symbol.location.range.start, lsp_types::Position::new(2, 11));
+
+        // Edits replace what the index knew about the file.
+        let content = "local M = {}\nfunction M.setup() end\nreturn M\n";
+        let (config, context) = backend.context_for(Some(&folder.join("ui.lua"))).await;
+        backend
+            .update_module(&folder.join("ui.lua"), content, &config, &context.resolver)
+            .await;
+        let names: Vec<String> = backend
+            .symbol(WorkspaceSymbolParams {
+                query: "ui.".to_string(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|symbol| symbol.name)
+            .collect();
+        assert!(names.contains(&"ui.setup".to_string()));
+        assert!(!names.contains(&"ui.initialize_ui".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_hover() {
+        let service = service();
+        let backend = service.inner();
+        let folder = fs::canonicalize("tests/modules").unwrap();
+        let main = open_module(backend, &folder, "main.lua").await;
+        open_module(backend, &folder, "ui.lua").await;
+        let hover = backend
+            .hover(HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri: main },
+                    position: lsp_types::Position::new(4, 5),
+                },
+                work_done_progress_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let HoverContents::Markup(contents) = hover.contents else {
+            panic!("expected markdown");
+        };
+        assert_eq!(
+            contents.value,
+            "```lua\n(field) function ui.render(theme)\n```\n\nDeclared in `ui.lua` at line 5."
+        );
+        assert_eq!(
+            hover.range.map(|r| (r.start, r.end)),
+            Some((
+                lsp_types::Position::new(4, 3),
+                lsp_types::Position::new(4, 9)
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rename_module_field() {
+        let service = service();
+        let backend = service.inner();
+        let folder = fs::canonicalize("tests/modules").unwrap();
+        let main = open_module(backend, &folder, "main.lua").await;
+        let ui = open_module(backend, &folder, "ui.lua").await;
+        let position = TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri: main.clone() },
+            position: lsp_types::Position::new(4, 5),
+        };
+        let Some(PrepareRenameResponse::Range(range)) =
+            backend.prepare_rename(position.clone()).await.unwrap()
+        else {
+            panic!("expected a range");
+        };
+        assert_eq!(range.start, lsp_types::Position::new(4, 3));
+        let params = |new_name: &str| RenameParams {
+            text_document_position: position.clone(),
+            new_name: new_name.to_string(),
+            work_done_progress_params: Default::default(),
+        };
+        let edit = backend.rename(params("draw")).await.unwrap().unwrap();
+        let changes = edit.changes.unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[&main][0].new_text, "draw");
+        assert_eq!(changes[&ui][0].range.start, lsp_types::Position::new(4, 2));
+
+        let err = backend.rename(params("end")).await.unwrap_err();
+        assert_eq!(err.message, "`end` is a Lua keyword");
+        let err = backend.rename(params("theme")).await.unwrap_err();
+        assert_eq!(err.message, "`theme` already exists");
+        let print = TextDocumentPositionParams {
+            position: lsp_types::Position::new(5, 1),
+            ..position
+        };
+        assert!(backend.prepare_rename(print).await.is_err());
+    }
 }
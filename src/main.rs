@@ -1,16 +1,37 @@
+mod analysis;
+mod checker;
 mod cli;
+mod completion;
 mod config;
+mod diagnostic;
+mod document;
 mod error;
+mod library;
+mod line_index;
+mod lint;
 mod lsp;
+mod modules;
+mod outline;
+mod output;
 mod parser;
+mod recovery;
+mod resolver;
+mod scope;
+mod signature;
+mod snippet;
+mod stdlib;
+mod suppression;
+mod symbols;
 mod workspace;
 
 use crate::cli::{CheckOptions, Command, LspOptions};
 use crate::error::LuascanError;
+use crate::output::OutputFormat;
 use anyhow::Result;
 use std::fs::File;
 use std::process;
 use std::sync::Arc;
+use tracing::{Level, event};
 use tracing_subscriber::EnvFilter;
 
 fn main() {
@@ -28,18 +49,27 @@ fn run() -> Result<()> {
 }
 
 fn handle_check(options: CheckOptions) -> Result<()> {
-    // let report = checker::run(&options)?;
-    //
-    // if report.diagnostics.is_empty() {
-    //     println!("Checked {} file(s); no issues found.", report.files_checked);
-    //     return Ok(());
-    // }
-    //
-    // for diagnostic in &report.diagnostics {
-    //     println!("{diagnostic}");
-    // }
-    //
-    unimplemented!("handle check")
+    let report = checker::run(&options)?;
+
+    if options.format == OutputFormat::Text && report.diagnostics.is_empty() {
+        println!(
+            "Checked {} file(s); no issues found.",
+            report.files_checked()
+        );
+        return Ok(());
+    }
+
+    let color = options.color.enabled();
+    println!("{}", output::render(&report, options.format, color)?);
+    if report.error_count() == 0 {
+        return Ok(());
+    }
+
+    Err(LuascanError::CheckFailed {
+        errors: report.error_count(),
+        files: report.files_checked(),
+    }
+    .into())
 }
 
 fn handle_lsp(options: LspOptions) -> Result<()> {
@@ -66,6 +96,7 @@ fn handle_lsp(options: LspOptions) -> Result<()> {
         .with_writer(log_file)
         .json()
         .init();
+    event!(Level::INFO, "start lsp with {:?}", options.config);
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
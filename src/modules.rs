@@ -0,0 +1,283 @@
+//! Dependency graph of the modules of a project: what each file requires,
+//! the shape of the table it returns and which files require it.
+
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use full_moon::parse_fallible;
+
+use crate::config::Config;
+use crate::diagnostic::Location;
+use crate::parser;
+use crate::resolver::Resolver;
+use crate::scope::{Access, Member, ScopeTree};
+
+/// A `require` that resolved to a file.
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub module: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub dependencies: Vec<Dependency>,
+    /// Fields of the returned table, or `None` when its shape is unknown.
+    pub exports: Option<Vec<Member>>,
+    /// Location of the returned value; `None` for scripts that return nothing.
+    pub returns: Option<Location>,
+    /// Globals the module assigns, each at its first assignment.
+    pub globals: Vec<Member>,
+}
+
+impl Module {
+    pub fn new(tree: &ScopeTree, resolver: &Resolver) -> Self {
+        Self {
+            dependencies: tree
+                .requires
+                .iter()
+                .filter_map(|r| {
+                    Some(Dependency {
+                        module: r.module.clone(),
+                        path: normalize(&resolver.resolve(&r.module)?),
+                    })
+                })
+                .collect(),
+            exports: tree
+                .exports()
+                .map(|fields| fields.into_iter().cloned().collect()),
+            returns: tree.export.as_ref().map(|export| export.loc.clone()),
+            globals: tree.globals().filter(|r| r.access == Access::Write).fold(
+                Vec::new(),
+                |mut globals: Vec<Member>, r| {
+                    if !globals.iter().any(|g| g.name == r.name) {
+                        globals.push(Member {
+                            name: r.name.clone(),
+                            loc: r.loc.clone(),
+                            assigned: true,
+                            value: r.value.clone(),
+                        });
+                    }
+                    globals
+                },
+            ),
+        }
+    }
+}
+
+/// Modules keyed by their canonical path.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleGraph {
+    modules: HashMap<PathBuf, Module>,
+    dependents: HashMap<PathBuf, BTreeSet<PathBuf>>,
+    /// Directories whose every module is in the graph, so that a module below
+    /// them without dependents is known to be unused.
+    indexed: Vec<PathBuf>,
+}
+
+/// The key of `path` in the graph. Files that cannot be canonicalized, such
+/// as unsaved buffers, keep their path.
+pub fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl ModuleGraph {
+    /// Records that every module below `dir` is in the graph.
+    pub fn mark_indexed(&mut self, dir: &Path) {
+        let dir = normalize(dir);
+        if !self.indexed.contains(&dir) {
+            self.indexed.push(dir);
+        }
+    }
+
+    pub fn unmark_indexed(&mut self, dir: &Path) {
+        let dir = normalize(dir);
+        self.indexed.retain(|indexed| *indexed != dir);
+    }
+
+    /// Whether every module that could require `path` is in the graph.
+    pub fn is_complete_for(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        self.indexed.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Parses `code` as the module at `path` and replaces what the graph knew
+    /// about it.
+    pub fn update(&mut self, path: &Path, code: &str, config: &Config, resolver: &Resolver) {
+        let result = parse_fallible(code, parser::lua_version(config.runtime.version));
+        let tree = ScopeTree::build(result.ast());
+        self.insert(path, Module::new(&tree, resolver));
+    }
+
+    pub fn insert(&mut self, path: &Path, module: Module) {
+        let path = normalize(path);
+        self.unlink(&path);
+        for dependency in &module.dependencies {
+            self.dependents
+                .entry(dependency.path.clone())
+                .or_default()
+                .insert(path.clone());
+        }
+        self.modules.insert(path, module);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        let path = normalize(path);
+        self.unlink(&path);
+        self.modules.remove(&path);
+    }
+
+    fn unlink(&mut self, path: &Path) {
+        let Some(old) = self.modules.get(path) else {
+            return;
+        };
+        for dependency in &old.dependencies {
+            if let Some(dependents) = self.dependents.get_mut(&dependency.path) {
+                dependents.remove(path);
+            }
+        }
+    }
+
+    /// Reads the files required by the graph's modules that it does not hold
+    /// yet, such as library modules, until every dependency is known.
+    pub fn load_dependencies(&mut self, config: &Config, resolver: &Resolver) {
+        let mut pending: Vec<PathBuf> = self.dependents.keys().cloned().collect();
+        while let Some(path) = pending.pop() {
+            if self.modules.contains_key(&path) {
+                continue;
+            }
+            let Ok(code) = fs::read_to_string(&path) else {
+                continue;
+            };
+            self.update(&path, &code, config, resolver);
+            if let Some(module) = self.modules.get(&path) {
+                pending.extend(module.dependencies.iter().map(|d| d.path.clone()));
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Module)> {
+        self.modules
+            .iter()
+            .map(|(path, module)| (path.as_path(), module))
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Module> {
+        self.modules.get(&normalize(path))
+    }
+
+    /// Files that require the module at `path`.
+    pub fn dependents(&self, path: &Path) -> impl Iterator<Item = &Path> {
+        self.dependents
+            .get(&normalize(path))
+            .into_iter()
+            .flatten()
+            .map(PathBuf::as_path)
+    }
+
+    /// The shortest chain of `require`s leading from `from` to `to`, as the
+    /// module names written at each step.
+    pub fn path_between(&self, from: &Path, to: &Path) -> Option<Vec<&str>> {
+        let (from, to) = (normalize(from), normalize(to));
+        let mut previous: HashMap<&Path, (&Path, &str)> = HashMap::new();
+        let mut queue = VecDeque::from([from.as_path()]);
+        while let Some(path) = queue.pop_front() {
+            for dependency in self
+                .modules
+                .get(path)
+                .into_iter()
+                .flat_map(|m| &m.dependencies)
+            {
+                let next = dependency.path.as_path();
+                if next == from || previous.contains_key(next) {
+                    continue;
+                }
+                previous.insert(next, (path, &dependency.module));
+                if next == to {
+                    let mut chain = Vec::new();
+                    let mut current = next;
+                    while let Some((prev, module)) = previous.get(current) {
+                        chain.push(*module);
+                        current = prev;
+                    }
+                    chain.reverse();
+                    return Some(chain);
+                }
+                queue.push_back(next);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> ModuleGraph {
+        let config = Config::default();
+        let resolver = Resolver::new(&config, [PathBuf::from("tests/modules")]);
+        let mut graph = ModuleGraph::default();
+        for name in ["main", "a"] {
+            let path = PathBuf::from(format!("tests/modules/{name}.lua"));
+            graph.update(
+                &path,
+                &fs::read_to_string(&path).unwrap(),
+                &config,
+                &resolver,
+            );
+        }
+        graph.load_dependencies(&config, &resolver);
+        graph
+    }
+
+    #[test]
+    fn test_exports_and_dependents() {
+        let graph = graph();
+        let ui = Path::new("tests/modules/ui.lua");
+        let mut exports: Vec<String> = graph
+            .get(ui)
+            .unwrap()
+            .exports
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|field| field.name.clone())
+            .collect();
+        exports.sort();
+        assert_eq!(exports, vec!["initialize_ui", "render", "theme"]);
+        let main = normalize(Path::new("tests/modules/main.lua"));
+        let dependents: Vec<&Path> = graph.dependents(ui).collect();
+        assert_eq!(dependents, vec![main.as_path()]);
+    }
+
+    #[test]
+    fn test_is_complete_for() {
+        let mut graph = graph();
+        let ui = Path::new("tests/modules/ui.lua");
+        assert!(!graph.is_complete_for(ui));
+        graph.mark_indexed(Path::new("tests/modules"));
+        assert!(graph.is_complete_for(ui));
+        assert!(!graph.is_complete_for(Path::new("tests/library/src/main.lua")));
+        graph.unmark_indexed(Path::new("tests/modules"));
+        assert!(!graph.is_complete_for(ui));
+    }
+
+    #[test]
+    fn test_path_between() {
+        let graph = graph();
+        let (a, b) = (
+            Path::new("tests/modules/a.lua"),
+            Path::new("tests/modules/b.lua"),
+        );
+        assert_eq!(graph.path_between(b, a), Some(vec!["a"]));
+        assert_eq!(graph.path_between(a, a), None);
+        assert_eq!(
+            graph.path_between(Path::new("tests/modules/main.lua"), b),
+            Some(vec!["a", "b"])
+        );
+    }
+}
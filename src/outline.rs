@@ -0,0 +1,318 @@
+//! Outline of a file for `textDocument/documentSymbol`: its functions and
+//! tables with their fields, nested as they are in the source.
+
+use full_moon::{
+    ast::{
+        Block, Expression, Field, FunctionBody, Index, LastStmt, Parameter, Prefix, Stmt, Suffix,
+        Var,
+    },
+    node::Node,
+    parse_fallible,
+    tokenizer::TokenReference,
+};
+
+use crate::config::RuntimeVersion;
+use crate::diagnostic::Location;
+use crate::parser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineKind {
+    Function,
+    /// A function declared with `:`.
+    Method,
+    Table,
+    /// The table the file returns.
+    Module,
+    Field,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutlineItem {
+    pub name: String,
+    pub kind: OutlineKind,
+    /// Parameter list of a function, as `(a, b)`.
+    pub detail: Option<String>,
+    /// The whole definition, including the children.
+    pub range: Location,
+    /// The name.
+    pub selection: Location,
+    pub children: Vec<OutlineItem>,
+}
+
+impl OutlineItem {
+    fn new(name: &TokenReference, kind: OutlineKind, node: &impl Node) -> Self {
+        let selection = Location::new(name.token().start_position(), name.token().end_position());
+        // A block a broken buffer never closes has no real end; it is
+        // extended to its children when they are attached.
+        let range = match (node.start_position(), node.end_position()) {
+            (Some(start), Some(end)) if start.bytes() <= end.bytes() => Location::new(start, end),
+            _ => selection.clone(),
+        };
+        Self {
+            name: name.token().to_string(),
+            kind,
+            detail: None,
+            range,
+            selection,
+            children: Vec::new(),
+        }
+    }
+
+    fn function(
+        name: &TokenReference,
+        kind: OutlineKind,
+        node: &impl Node,
+        body: &FunctionBody,
+    ) -> Self {
+        let params: Vec<String> = body
+            .parameters()
+            .iter()
+            .filter_map(|parameter| match parameter {
+                Parameter::Name(name) => Some(name.token().to_string()),
+                Parameter::Ellipsis(_) => Some("...".to_string()),
+                _ => None,
+            })
+            .collect();
+        let mut item = Self::new(name, kind, node);
+        item.detail = Some(format!("({})", params.join(", ")));
+        for child in outline_block(body.block()) {
+            item.attach(child);
+        }
+        item
+    }
+
+    /// The item for `name` defined as `value`: functions and tables, or any
+    /// value for a field.
+    fn of_value(
+        name: &TokenReference,
+        value: &Expression,
+        node: &impl Node,
+        field: bool,
+    ) -> Option<Self> {
+        match value {
+            Expression::Function(function) => Some(Self::function(
+                name,
+                OutlineKind::Function,
+                node,
+                function.body(),
+            )),
+            Expression::TableConstructor(table) => {
+                let mut item = Self::new(name, OutlineKind::Table, node);
+                for field in table.fields() {
+                    if let Field::NameKey { key, value, .. } = field
+                        && let Some(child) = Self::of_value(key, value, field, true)
+                    {
+                        item.attach(child);
+                    }
+                }
+                Some(item)
+            }
+            _ if field => Some(Self::new(name, OutlineKind::Field, node)),
+            _ => None,
+        }
+    }
+
+    fn attach(&mut self, child: OutlineItem) {
+        if child.range.byte_end > self.range.byte_end {
+            self.range.line_end = child.range.line_end;
+            self.range.col_end = child.range.col_end;
+            self.range.byte_end = child.range.byte_end;
+        }
+        self.children.push(child);
+    }
+}
+
+/// Outline of `code`, which may not parse.
+pub fn outline(code: &str, version: RuntimeVersion) -> Vec<OutlineItem> {
+    let result = parse_fallible(code, parser::lua_version(version));
+    let ast = result.ast();
+    let mut items = outline_block(ast.nodes());
+    if let Some(LastStmt::Return(ret)) = ast.nodes().last_stmt()
+        && let (1, Some(Expression::Var(Var::Name(name)))) =
+            (ret.returns().len(), ret.returns().iter().next())
+        && let Some(item) = items
+            .iter_mut()
+            .rev()
+            .find(|item| item.name == name.token().to_string())
+        && item.kind == OutlineKind::Table
+    {
+        item.kind = OutlineKind::Module;
+    }
+    items
+}
+
+fn outline_block(block: &Block) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    for stmt in block.stmts() {
+        outline_stmt(stmt, &mut items);
+    }
+    items
+}
+
+/// Adds `child` to the table `owner` among `items`, the last one declared.
+/// Returns the child back when there is no such table.
+fn attach_to(items: &mut [OutlineItem], owner: &str, child: OutlineItem) -> Option<OutlineItem> {
+    match items
+        .iter_mut()
+        .rev()
+        .find(|item| item.name == owner && item.kind == OutlineKind::Table)
+    {
+        Some(table) => {
+            table.attach(child);
+            None
+        }
+        None => Some(child),
+    }
+}
+
+fn outline_stmt(stmt: &Stmt, items: &mut Vec<OutlineItem>) {
+    match stmt {
+        Stmt::LocalFunction(function) => items.push(OutlineItem::function(
+            function.name(),
+            OutlineKind::Function,
+            stmt,
+            function.body(),
+        )),
+        Stmt::FunctionDeclaration(declaration) => {
+            let name = declaration.name();
+            let names: Vec<&TokenReference> = name.names().iter().collect();
+            let (kind, last) = match name.method_name() {
+                Some(method) => (OutlineKind::Method, method),
+                None => match names.last() {
+                    Some(last) => (OutlineKind::Function, *last),
+                    None => return,
+                },
+            };
+            let item = OutlineItem::function(last, kind, stmt, declaration.body());
+            // `function M.f()` and `function M:f()` go under the table `M`;
+            // other functions keep their full name.
+            let is_field = names.len() + usize::from(name.method_name().is_some()) == 2;
+            let rest = match names.first() {
+                Some(owner) if is_field => attach_to(items, &owner.token().to_string(), item),
+                _ => Some(item),
+            };
+            if let Some(mut item) = rest {
+                item.name = name.to_string().trim().to_string();
+                items.push(item);
+            }
+        }
+        Stmt::LocalAssignment(assignment) => {
+            for (name, value) in assignment.names().iter().zip(assignment.expressions()) {
+                items.extend(OutlineItem::of_value(name, value, stmt, false));
+            }
+        }
+        Stmt::Assignment(assignment) => {
+            for (var, value) in assignment.variables().iter().zip(assignment.expressions()) {
+                match var {
+                    Var::Name(name) => {
+                        items.extend(OutlineItem::of_value(name, value, stmt, false))
+                    }
+                    Var::Expression(expr) => {
+                        let mut suffixes = expr.suffixes();
+                        if let (
+                            Prefix::Name(owner),
+                            Some(Suffix::Index(Index::Dot { name, .. })),
+                            None,
+                        ) = (expr.prefix(), suffixes.next(), suffixes.next())
+                            && let Some(child) = OutlineItem::of_value(name, value, stmt, true)
+                        {
+                            // Fields of tables the outline does not show,
+                            // such as `self.x`, are left out.
+                            attach_to(items, &owner.token().to_string(), child);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Stmt::Do(stmt) => items.extend(outline_block(stmt.block())),
+        Stmt::GenericFor(stmt) => items.extend(outline_block(stmt.block())),
+        Stmt::NumericFor(stmt) => items.extend(outline_block(stmt.block())),
+        Stmt::Repeat(stmt) => items.extend(outline_block(stmt.block())),
+        Stmt::While(stmt) => items.extend(outline_block(stmt.block())),
+        Stmt::If(stmt) => {
+            items.extend(outline_block(stmt.block()));
+            for else_if in stmt.else_if().into_iter().flatten() {
+                items.extend(outline_block(else_if.block()));
+            }
+            if let Some(block) = stmt.else_block() {
+                items.extend(outline_block(block));
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Items as `name kind line` with children indented below their parent.
+    fn render(items: &[OutlineItem], depth: usize, out: &mut Vec<String>) {
+        for item in items {
+            out.push(format!(
+                "{}{} {:?} {}-{}",
+                "  ".repeat(depth),
+                item.name,
+                item.kind,
+                item.range.line_start,
+                item.range.line_end
+            ));
+            render(&item.children, depth + 1, out);
+        }
+    }
+
+    fn lines(code: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        render(&outline(code, RuntimeVersion::Lua54), 0, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_module_outline() {
+        let code = fs::read_to_string("tests/modules/ui.lua").unwrap();
+        assert_eq!(
+            lines(&code),
+            vec![
+                "M Module 1-7",
+                "  theme Field 1-1",
+                "  initialize_ui Function 3-3",
+                "  render Function 5-7",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_outline() {
+        let code = "local Class = {}\nfunction Class:new(o)\n  local function helper() end\n  return o\nend\nfunction global(a, ...)\n  if a then\n    local t = { f = function() end, n = 1 }\n  end\nend\nfunction other.f() end\nlocal x = 1\n";
+        assert_eq!(
+            lines(code),
+            vec![
+                "Class Table 1-5",
+                "  new Method 2-5",
+                "    helper Function 3-3",
+                "global Function 6-10",
+                "  t Table 8-8",
+                "    f Function 8-8",
+                "    n Field 8-8",
+                "other.f Function 11-11",
+            ]
+        );
+        let items = outline(code, RuntimeVersion::Lua54);
+        assert_eq!(items[1].detail.as_deref(), Some("(a, ...)"));
+    }
+
+    #[test]
+    fn test_outline_of_broken_buffer() {
+        let code = "local M = {}\nfunction M.run()\n  local function inner()\n";
+        assert_eq!(
+            lines(code),
+            vec![
+                "M Table 1-3",
+                "  run Function 2-3",
+                "    inner Function 3-3"
+            ]
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use anyhow::Result;
+use std::{
+    fmt::Write,
+    io::{self, IsTerminal},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    checker::{CheckReport, FileDiagnostic},
+    diagnostic::{Location, RuleCode, Severity, Tag},
+    snippet,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Sarif,
+    Checkstyle,
+    Junit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// `auto` colors only when stdout is a terminal and `NO_COLOR` is unset.
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+pub fn render(report: &CheckReport, format: OutputFormat, color: bool) -> Result<String> {
+    let rendered = match format {
+        OutputFormat::Text => text(report, color),
+        OutputFormat::Json => serde_json::to_string_pretty(&JsonReport::new(report))?,
+        OutputFormat::Sarif => serde_json::to_string_pretty(&sarif(report))?,
+        OutputFormat::Checkstyle => checkstyle(report),
+        OutputFormat::Junit => junit(report),
+    };
+    Ok(rendered)
+}
+
+fn text(report: &CheckReport, color: bool) -> String {
+    report
+        .diagnostics
+        .iter()
+        .map(|d| {
+            let source = report.sources.get(&d.path).map_or("", String::as_str);
+            snippet::render(&d.path, source, &d.diagnostic, color)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport {
+    files_checked: usize,
+    diagnostics: Vec<JsonDiagnostic>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDiagnostic {
+    path: String,
+    range: JsonRange,
+    severity: &'static str,
+    code: &'static str,
+    rule: &'static str,
+    message: String,
+    related: Vec<JsonRelated>,
+    fixes: Vec<JsonFix>,
+    tags: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRelated {
+    path: String,
+    range: JsonRange,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFix {
+    range: JsonRange,
+    replacement: String,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRange {
+    start: JsonPosition,
+    end: JsonPosition,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonPosition {
+    line: usize,
+    column: usize,
+}
+
+impl JsonRange {
+    fn new(loc: &Location) -> Self {
+        Self {
+            start: JsonPosition {
+                line: loc.line_start,
+                column: loc.col_start,
+            },
+            end: JsonPosition {
+                line: loc.line_end,
+                column: loc.col_end,
+            },
+        }
+    }
+}
+
+impl JsonReport {
+    fn new(report: &CheckReport) -> Self {
+        Self {
+            files_checked: report.files_checked(),
+            diagnostics: report
+                .diagnostics
+                .iter()
+                .map(|d| JsonDiagnostic {
+                    path: display_path(&d.path),
+                    range: JsonRange::new(&d.diagnostic.loc),
+                    severity: d.diagnostic.severity.as_str(),
+                    code: d.diagnostic.code.code(),
+                    rule: d.diagnostic.code.name(),
+                    message: d.diagnostic.msg.clone(),
+                    related: d
+                        .diagnostic
+                        .related
+                        .iter()
+                        .map(|r| JsonRelated {
+                            path: display_path(r.path.as_deref().unwrap_or(&d.path)),
+                            range: JsonRange::new(&r.loc),
+                            message: r.msg.clone(),
+                        })
+                        .collect(),
+                    fixes: d
+                        .diagnostic
+                        .fixes
+                        .iter()
+                        .map(|f| JsonFix {
+                            range: JsonRange::new(&f.loc),
+                            replacement: f.replacement.clone(),
+                            message: f.msg.clone(),
+                        })
+                        .collect(),
+                    tags: d.diagnostic.tags.iter().map(|t| tag_name(*t)).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn tag_name(tag: Tag) -> &'static str {
+    match tag {
+        Tag::Unnecessary => "unnecessary",
+        Tag::Deprecated => "deprecated",
+    }
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info | Severity::Hint => "note",
+    }
+}
+
+fn sarif_location(path: &Path, loc: &Location) -> serde_json::Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": display_path(path) },
+            "region": {
+                "startLine": loc.line_start,
+                "startColumn": loc.col_start,
+                "endLine": loc.line_end,
+                "endColumn": loc.col_end,
+            }
+        }
+    })
+}
+
+fn sarif(report: &CheckReport) -> serde_json::Value {
+    let results: Vec<serde_json::Value> = report
+        .diagnostics
+        .iter()
+        .map(|d| {
+            let related: Vec<serde_json::Value> = d
+                .diagnostic
+                .related
+                .iter()
+                .map(|r| {
+                    let mut location =
+                        sarif_location(r.path.as_deref().unwrap_or(&d.path), &r.loc);
+                    location["message"] = json!({ "text": r.msg });
+                    location
+                })
+                .collect();
+            let fixes: Vec<serde_json::Value> = d
+                .diagnostic
+                .fixes
+                .iter()
+                .map(|f| {
+                    json!({
+                        "description": { "text": f.msg },
+                        "artifactChanges": [{
+                            "artifactLocation": { "uri": display_path(&d.path) },
+                            "replacements": [{
+                                "deletedRegion": sarif_location(&d.path, &f.loc)["physicalLocation"]["region"],
+                                "insertedContent": { "text": f.replacement },
+                            }]
+                        }]
+                    })
+                })
+                .collect();
+            json!({
+                "ruleId": d.diagnostic.code.code(),
+                "level": sarif_level(d.diagnostic.severity),
+                "message": { "text": d.diagnostic.msg },
+                "locations": [sarif_location(&d.path, &d.diagnostic.loc)],
+                "relatedLocations": related,
+                "fixes": fixes,
+            })
+        })
+        .collect();
+    let rules: Vec<serde_json::Value> = RuleCode::ALL
+        .iter()
+        .map(|code| {
+            json!({
+                "id": code.code(),
+                "name": code.name(),
+                "shortDescription": { "text": code.description() },
+                "helpUri": code.docs_url(),
+                "defaultConfiguration": { "level": sarif_level(code.default_severity()) },
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "luascan",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }]
+    })
+}
+
+fn checkstyle(report: &CheckReport) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<checkstyle version=\"4.3\">\n");
+    for path in &report.files {
+        let _ = writeln!(out, "  <file name=\"{}\">", escape_xml(&display_path(path)));
+        for d in diagnostics_of(report, path) {
+            let loc = &d.diagnostic.loc;
+            let _ = writeln!(
+                out,
+                "    <error line=\"{}\" column=\"{}\" severity=\"{}\" message=\"{}\" source=\"luascan.{}\"/>",
+                loc.line_start,
+                loc.col_start,
+                checkstyle_severity(d.diagnostic.severity),
+                escape_xml(&d.diagnostic.msg),
+                d.diagnostic.code.name()
+            );
+        }
+        out.push_str("  </file>\n");
+    }
+    out.push_str("</checkstyle>");
+    out
+}
+
+fn checkstyle_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info | Severity::Hint => "info",
+    }
+}
+
+fn junit(report: &CheckReport) -> String {
+    let failures = report
+        .files
+        .iter()
+        .filter(|path| {
+            diagnostics_of(report, path).any(|d| d.diagnostic.severity == Severity::Error)
+        })
+        .count();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<testsuites name=\"luascan\" tests=\"{}\" failures=\"{}\">",
+        report.files_checked(),
+        failures
+    );
+    let _ = writeln!(
+        out,
+        "  <testsuite name=\"luascan\" tests=\"{}\" failures=\"{}\">",
+        report.files_checked(),
+        failures
+    );
+    for path in &report.files {
+        let name = escape_xml(&display_path(path));
+        let diagnostics: Vec<&FileDiagnostic> = diagnostics_of(report, path)
+            .filter(|d| d.diagnostic.severity == Severity::Error)
+            .collect();
+        if diagnostics.is_empty() {
+            let _ = writeln!(out, "    <testcase classname=\"luascan\" name=\"{name}\"/>");
+            continue;
+        }
+        let _ = writeln!(out, "    <testcase classname=\"luascan\" name=\"{name}\">");
+        for d in diagnostics {
+            let _ = writeln!(
+                out,
+                "      <failure type=\"{}\" message=\"{}\">{}</failure>",
+                d.diagnostic.code.name(),
+                escape_xml(&d.diagnostic.msg),
+                escape_xml(&d.to_string())
+            );
+        }
+        out.push_str("    </testcase>\n");
+    }
+    out.push_str("  </testsuite>\n</testsuites>");
+    out
+}
+
+fn diagnostics_of<'a>(
+    report: &'a CheckReport,
+    path: &'a Path,
+) -> impl Iterator<Item = &'a FileDiagnostic> {
+    report.diagnostics.iter().filter(move |d| d.path == path)
+}
+
+fn display_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::LuascanDiagnostic;
+    use pretty_assertions::assert_eq;
+    use std::{collections::HashMap, path::PathBuf};
+
+    fn report() -> CheckReport {
+        CheckReport {
+            files: vec![PathBuf::from("ok.lua"), PathBuf::from("bad.lua")],
+            sources: HashMap::new(),
+            diagnostics: vec![FileDiagnostic {
+                path: PathBuf::from("bad.lua"),
+                diagnostic: LuascanDiagnostic::new(
+                    RuleCode::UnexpectedToken,
+                    Location {
+                        line_start: 2,
+                        line_end: 2,
+                        col_start: 5,
+                        col_end: 8,
+                        byte_start: 16,
+                        byte_end: 19,
+                    },
+                    "expected `end` & got <eof>",
+                ),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_render_json() {
+        let rendered = render(&report(), OutputFormat::Json, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(value["files_checked"], 2);
+        assert_eq!(value["diagnostics"][0]["path"], "bad.lua");
+        assert_eq!(value["diagnostics"][0]["range"]["start"]["line"], 2);
+        assert_eq!(value["diagnostics"][0]["range"]["end"]["column"], 8);
+        assert_eq!(value["diagnostics"][0]["severity"], "error");
+        assert_eq!(value["diagnostics"][0]["code"], "E001");
+        assert_eq!(value["diagnostics"][0]["rule"], "unexpected-token");
+    }
+
+    #[test]
+    fn test_render_sarif() {
+        let rendered = render(&report(), OutputFormat::Sarif, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        let result = &value["runs"][0]["results"][0];
+        assert_eq!(value["version"], "2.1.0");
+        assert_eq!(result["ruleId"], "E001");
+        assert_eq!(
+            value["runs"][0]["tool"]["driver"]["rules"][0]["helpUri"],
+            RuleCode::UnexpectedToken.docs_url()
+        );
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["region"]["startColumn"],
+            5
+        );
+    }
+
+    #[test]
+    fn test_render_checkstyle() {
+        let rendered = render(&report(), OutputFormat::Checkstyle, false).unwrap();
+        assert!(rendered.contains("<file name=\"ok.lua\">\n  </file>"));
+        assert!(rendered.contains(
+            "<error line=\"2\" column=\"5\" severity=\"error\" message=\"expected `end` &amp; got &lt;eof&gt;\""
+        ));
+    }
+
+    #[test]
+    fn test_render_junit() {
+        let rendered = render(&report(), OutputFormat::Junit, false).unwrap();
+        assert!(rendered.contains("tests=\"2\" failures=\"1\""));
+        assert!(rendered.contains("<testcase classname=\"luascan\" name=\"ok.lua\"/>"));
+        assert!(rendered.contains("<failure type=\"unexpected-token\""));
+    }
+}
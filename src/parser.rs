@@ -1,60 +1,97 @@
 use crate::config::RuntimeVersion;
-use full_moon::{LuaVersion, parse_fallible};
+use crate::diagnostic::{Location, LuascanDiagnostic, RuleCode};
+use crate::recovery;
+use full_moon::{LuaVersion, ast::Ast, parse_fallible};
 use tracing::{Level, event};
 
-#[derive(Debug, Clone)]
-pub struct Location {
-    pub line_start: usize,
-    pub line_end: usize,
-    pub col_start: usize,
-    pub col_end: usize,
-}
-
-#[derive(Debug, Clone)]
-pub struct LuascanDiagnostic {
-    pub loc: Location,
-    pub msg: String,
-}
-
-pub fn parse(code: &str, version: RuntimeVersion) -> Vec<LuascanDiagnostic> {
-    let version = match version {
+pub fn lua_version(version: RuntimeVersion) -> LuaVersion {
+    match version {
         RuntimeVersion::Lua51 => LuaVersion::lua51(),
         RuntimeVersion::Lua52 => LuaVersion::lua52(),
         RuntimeVersion::Lua53 => LuaVersion::lua53(),
         RuntimeVersion::Lua54 => LuaVersion::lua54(),
-        _ => panic!("failed version"),
-    };
+        RuntimeVersion::Luajit => LuaVersion::luajit(),
+    }
+}
+
+/// Parses `code` into a possibly partial AST along with its syntax errors.
+pub fn parse(code: &str, version: RuntimeVersion) -> (Ast, Vec<LuascanDiagnostic>) {
+    let version = lua_version(version);
     let ast = parse_fallible(code, version);
     let mut ret = Vec::new();
     for e in ast.errors().iter() {
         match e {
             full_moon::Error::AstError(ast_err) => {
                 let token = ast_err.token().clone();
-                let loc = Location {
-                    line_start: token.start_position().line(),
-                    line_end: token.end_position().line(),
-                    col_start: token.start_position().character(),
-                    col_end: token.end_position().character(),
-                };
-                let log_msg = format!("parse ast-error {:?}", ast_err);
+                let loc = Location::new(token.start_position(), token.end_position());
+                let log_msg = format!("parse ast-error at {} {:?}", loc, ast_err);
                 event!(Level::INFO, "{}", log_msg);
                 let msg = ast_err.error_message().to_string().clone();
-                ret.push(LuascanDiagnostic { loc, msg });
+                ret.push(LuascanDiagnostic::new(RuleCode::UnexpectedToken, loc, msg));
             }
             full_moon::Error::TokenizerError(tkn_err) => {
                 let range = tkn_err.range();
-                let loc = Location {
-                    line_start: range.0.line(),
-                    line_end: range.1.line(),
-                    col_start: range.0.character(),
-                    col_end: range.1.character(),
-                };
-                let log_msg = format!("parse token-error {:?}", tkn_err);
+                let loc = Location::new(range.0, range.1);
+                let log_msg = format!("parse token-error at {} {:?}", loc, tkn_err);
                 event!(Level::INFO, "{}", log_msg);
                 let msg = tkn_err.error().to_string();
-                ret.push(LuascanDiagnostic { loc, msg });
+                ret.push(LuascanDiagnostic::new(RuleCode::MalformedToken, loc, msg));
             }
         }
     }
-    ret
+    let diagnostics = recovery::recover(code, version, ret);
+    (ast.into_ast(), diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOTO: &str = "for i = 1, 3 do\n  goto continue\n  ::continue::\nend\n";
+    const INTEGER_DIVISION: &str = "local x = 7 // 2\n";
+    const BITWISE: &str = "local x = (1 << 4) | (3 & 2) ~ 1\n";
+    const ATTRIBS: &str = "local x <const> = 1\nlocal f <close> = nil\n";
+    const LUAJIT_NUMBERS: &str = "local x = 1ULL\nlocal y = 2LL\n";
+
+    fn accepts(code: &str, version: RuntimeVersion) -> bool {
+        parse(code, version).1.is_empty()
+    }
+
+    #[test]
+    fn test_parse_lua51() {
+        assert!(accepts("local t = {}\nprint(#t)\n", RuntimeVersion::Lua51));
+        assert!(!accepts(GOTO, RuntimeVersion::Lua51));
+        assert!(!accepts(INTEGER_DIVISION, RuntimeVersion::Lua51));
+    }
+
+    #[test]
+    fn test_parse_lua52() {
+        assert!(accepts(GOTO, RuntimeVersion::Lua52));
+        assert!(!accepts(INTEGER_DIVISION, RuntimeVersion::Lua52));
+        assert!(!accepts(BITWISE, RuntimeVersion::Lua52));
+    }
+
+    #[test]
+    fn test_parse_lua53() {
+        assert!(accepts(GOTO, RuntimeVersion::Lua53));
+        assert!(accepts(INTEGER_DIVISION, RuntimeVersion::Lua53));
+        assert!(accepts(BITWISE, RuntimeVersion::Lua53));
+        assert!(!accepts(ATTRIBS, RuntimeVersion::Lua53));
+    }
+
+    #[test]
+    fn test_parse_lua54() {
+        assert!(accepts(GOTO, RuntimeVersion::Lua54));
+        assert!(accepts(INTEGER_DIVISION, RuntimeVersion::Lua54));
+        assert!(accepts(BITWISE, RuntimeVersion::Lua54));
+        assert!(accepts(ATTRIBS, RuntimeVersion::Lua54));
+    }
+
+    #[test]
+    fn test_parse_luajit() {
+        assert!(accepts(GOTO, RuntimeVersion::Luajit));
+        assert!(accepts(LUAJIT_NUMBERS, RuntimeVersion::Luajit));
+        assert!(!accepts(LUAJIT_NUMBERS, RuntimeVersion::Lua51));
+        assert!(!accepts(ATTRIBS, RuntimeVersion::Luajit));
+    }
 }
@@ -0,0 +1,413 @@
+//! Turns the raw errors of `parse_fallible` into fewer, more helpful ones.
+//!
+//! A token-level pass recognises common mistakes (`!=`, `+=`, `=` in a
+//! condition) and matches block openers with their `end`, so a missing `end`
+//! can point back at the block that was never closed. Raw errors that are
+//! only a consequence of an earlier one are dropped.
+
+use full_moon::{
+    LuaVersion,
+    tokenizer::{Lexer, LexerResult, Position, Symbol, TokenType},
+};
+
+use crate::diagnostic::{Location, LuascanDiagnostic, RuleCode};
+use crate::snippet::TAB_WIDTH;
+
+#[derive(Debug, Clone)]
+struct Tok {
+    text: String,
+    symbol: Option<Symbol>,
+    start: Position,
+    end: Position,
+}
+
+impl Tok {
+    fn is(&self, symbol: Symbol) -> bool {
+        self.symbol == Some(symbol)
+    }
+
+    fn loc(&self) -> Location {
+        Location::new(self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Opener {
+    tok: Tok,
+    indent: usize,
+}
+
+pub fn recover(
+    code: &str,
+    version: LuaVersion,
+    raw: Vec<LuascanDiagnostic>,
+) -> Vec<LuascanDiagnostic> {
+    let tokens = lex(code, version);
+    let mut found = Vec::new();
+    found.extend(not_equal_operators(&tokens));
+    found.extend(compound_assignments(code, &tokens));
+    found.extend(assignments_in_conditions(&tokens));
+    let unclosed = unclosed_block(code, &tokens);
+
+    let mut ret: Vec<LuascanDiagnostic> = Vec::new();
+    for d in raw {
+        let is_missing_end =
+            d.msg.starts_with("expected `end`") || d.msg.starts_with("expected `until`");
+        if is_missing_end && unclosed.is_some() {
+            continue;
+        }
+        let at_eof = d.loc.byte_start >= code.trim_end().len();
+        let cascades = found
+            .iter()
+            .chain(ret.iter())
+            .any(|prev| at_eof || prev.loc.line_start == d.loc.line_start);
+        if !cascades {
+            ret.push(d);
+        }
+    }
+    ret.extend(found);
+    ret.extend(unclosed);
+    ret.sort_by_key(|d| d.loc.byte_start);
+    ret
+}
+
+fn lex(code: &str, version: LuaVersion) -> Vec<Tok> {
+    let mut lexer = Lexer::new(code, version);
+    let mut tokens = Vec::new();
+    while let Some(result) = lexer.consume() {
+        match result {
+            LexerResult::Ok(token) | LexerResult::Recovered(token, _) => {
+                let token = token.token();
+                if matches!(token.token_type(), TokenType::Eof) {
+                    tokens.push(Tok {
+                        text: String::new(),
+                        symbol: None,
+                        start: token.start_position(),
+                        end: token.end_position(),
+                    });
+                    break;
+                }
+                tokens.push(Tok {
+                    text: token.to_string(),
+                    symbol: match token.token_type() {
+                        TokenType::Symbol { symbol } => Some(*symbol),
+                        _ => None,
+                    },
+                    start: token.start_position(),
+                    end: token.end_position(),
+                });
+            }
+            LexerResult::Fatal(errors) => {
+                for e in errors {
+                    let (start, end) = e.range();
+                    tokens.push(Tok {
+                        text: code
+                            .get(start.bytes()..end.bytes())
+                            .unwrap_or_default()
+                            .to_string(),
+                        symbol: None,
+                        start,
+                        end,
+                    });
+                }
+            }
+        }
+    }
+    tokens
+}
+
+fn adjacent(a: &Tok, b: &Tok) -> bool {
+    a.end.bytes() == b.start.bytes()
+}
+
+fn not_equal_operators(tokens: &[Tok]) -> Vec<LuascanDiagnostic> {
+    tokens
+        .windows(2)
+        .filter(|w| w[0].text == "!" && w[1].is(Symbol::Equal) && adjacent(&w[0], &w[1]))
+        .map(|w| {
+            let loc = Location::new(w[0].start, w[1].end);
+            LuascanDiagnostic::new(
+                RuleCode::NotEqualOperator,
+                loc.clone(),
+                "expected `~=`: `!=` is not a Lua operator",
+            )
+            .with_fix(loc, "~=", "replace `!=` with `~=`")
+        })
+        .collect()
+}
+
+fn compound_operator(tok: &Tok) -> Option<&'static str> {
+    match tok.symbol? {
+        Symbol::Plus => Some("+"),
+        Symbol::Minus => Some("-"),
+        Symbol::Star => Some("*"),
+        Symbol::Slash => Some("/"),
+        Symbol::Percent => Some("%"),
+        Symbol::Caret => Some("^"),
+        Symbol::TwoDots => Some(".."),
+        Symbol::DoubleSlash => Some("//"),
+        _ => None,
+    }
+}
+
+fn compound_assignments(code: &str, tokens: &[Tok]) -> Vec<LuascanDiagnostic> {
+    let mut ret = Vec::new();
+    for (i, w) in tokens.windows(2).enumerate() {
+        let Some(op) = compound_operator(&w[0]) else {
+            continue;
+        };
+        if !w[1].is(Symbol::Equal) || !adjacent(&w[0], &w[1]) {
+            continue;
+        }
+        let loc = Location::new(w[0].start, w[1].end);
+        let mut diagnostic = LuascanDiagnostic::new(
+            RuleCode::CompoundAssignment,
+            loc,
+            format!("expected `=`: compound assignment `{op}=` is not valid Lua"),
+        );
+        if let Some((range, replacement)) = expand_compound(code, tokens, i, op) {
+            let loc = Location::new(tokens[range.0].start, tokens[range.1].end);
+            diagnostic = diagnostic.with_fix(loc, replacement, format!("rewrite `{op}=` as `=`"));
+        }
+        ret.push(diagnostic);
+    }
+    ret
+}
+
+/// Builds `x = x + (rhs)` for a compound assignment whose target is a plain
+/// variable or field at the start of its line and whose value ends the line.
+fn expand_compound(
+    code: &str,
+    tokens: &[Tok],
+    op_index: usize,
+    op: &str,
+) -> Option<((usize, usize), String)> {
+    let line = tokens[op_index].start.line();
+    let first = tokens[..op_index]
+        .iter()
+        .rposition(|t| t.start.line() != line)
+        .map_or(0, |i| i + 1);
+    if first == op_index {
+        return None;
+    }
+    let target = &tokens[first..op_index];
+    let simple = target.iter().all(|t| {
+        t.symbol.is_none_or(|s| matches!(s, Symbol::Dot))
+            && !t.text.is_empty()
+            && t.text
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    });
+    if !simple {
+        return None;
+    }
+    let value_start = op_index + 2;
+    let value_end = tokens[value_start..]
+        .iter()
+        .position(|t| t.start.line() != line || t.text.is_empty() || t.is(Symbol::Semicolon))
+        .map(|n| value_start + n)?;
+    if value_end == value_start {
+        return None;
+    }
+    let lhs = &code[target[0].start.bytes()..target[target.len() - 1].end.bytes()];
+    let rhs = &code[tokens[value_start].start.bytes()..tokens[value_end - 1].end.bytes()];
+    let rhs = if value_end - value_start > 1 {
+        format!("({rhs})")
+    } else {
+        rhs.to_string()
+    };
+    Some(((first, value_end - 1), format!("{lhs} = {lhs} {op} {rhs}")))
+}
+
+fn assignments_in_conditions(tokens: &[Tok]) -> Vec<LuascanDiagnostic> {
+    let mut ret = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let terminator = if tokens[i].is(Symbol::If) || tokens[i].is(Symbol::ElseIf) {
+            Symbol::Then
+        } else if tokens[i].is(Symbol::While) {
+            Symbol::Do
+        } else {
+            i += 1;
+            continue;
+        };
+        let mut depth = 0usize;
+        i += 1;
+        while i < tokens.len() && !tokens[i].is(terminator) {
+            let tok = &tokens[i];
+            match tok.symbol {
+                Some(Symbol::LeftParen | Symbol::LeftBracket | Symbol::LeftBrace) => depth += 1,
+                Some(Symbol::RightParen | Symbol::RightBracket | Symbol::RightBrace) => {
+                    depth = depth.saturating_sub(1)
+                }
+                Some(Symbol::Function | Symbol::End) => break,
+                Some(Symbol::Equal) if depth == 0 => {
+                    let after_operator = i > 0
+                        && adjacent(&tokens[i - 1], tok)
+                        && (tokens[i - 1].text == "!"
+                            || compound_operator(&tokens[i - 1]).is_some());
+                    if !after_operator {
+                        ret.push(
+                            LuascanDiagnostic::new(
+                                RuleCode::AssignmentInCondition,
+                                tok.loc(),
+                                "expected `==`: `=` assigns and cannot be used in a condition",
+                            )
+                            .with_fix(
+                                tok.loc(),
+                                "==",
+                                "compare with `==`",
+                            ),
+                        );
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+    ret
+}
+
+fn indent_of(code: &str, tok: &Tok) -> usize {
+    let line_start = code[..tok.start.bytes()].rfind('\n').map_or(0, |i| i + 1);
+    code[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+/// Matches block openers with their closers. When blocks are left open at the
+/// end of the file, blames the one whose `end` was most likely forgotten: an
+/// `end` indented like an outer block suggests the inner ones lack theirs.
+fn unclosed_block(code: &str, tokens: &[Tok]) -> Option<LuascanDiagnostic> {
+    let mut stack: Vec<Opener> = Vec::new();
+    let mut pending_loops = 0usize;
+    let mut suspect: Option<Opener> = None;
+    for tok in tokens {
+        let Some(symbol) = tok.symbol else {
+            continue;
+        };
+        match symbol {
+            Symbol::Function | Symbol::If | Symbol::Repeat => stack.push(Opener {
+                tok: tok.clone(),
+                indent: indent_of(code, tok),
+            }),
+            Symbol::While | Symbol::For => {
+                pending_loops += 1;
+                stack.push(Opener {
+                    tok: tok.clone(),
+                    indent: indent_of(code, tok),
+                });
+            }
+            Symbol::Do if pending_loops > 0 => pending_loops -= 1,
+            Symbol::Do => stack.push(Opener {
+                tok: tok.clone(),
+                indent: indent_of(code, tok),
+            }),
+            Symbol::End | Symbol::Until => {
+                let indent = indent_of(code, tok);
+                if suspect.is_none()
+                    && let Some(top) = stack.last()
+                    && top.indent > indent
+                    && stack[..stack.len() - 1]
+                        .iter()
+                        .any(|outer| outer.indent == indent)
+                {
+                    suspect = Some(top.clone());
+                }
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    let eof = tokens.last()?;
+    let blamed = suspect.filter(|_| !stack.is_empty()).or(stack.pop())?;
+    let (keyword, closer) = match blamed.tok.symbol {
+        Some(Symbol::Repeat) => ("repeat", "until"),
+        _ => (blamed.tok.text.as_str(), "end"),
+    };
+    Some(
+        LuascanDiagnostic::new(
+            RuleCode::UnclosedBlock,
+            blamed.tok.loc(),
+            format!(
+                "expected `{closer}` to close `{keyword}` block started at line {}",
+                blamed.tok.start.line()
+            ),
+        )
+        .with_related(eof.loc(), format!("reached end of file without `{closer}`")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::RuntimeVersion;
+    use crate::parser::parse;
+    use pretty_assertions::assert_eq;
+
+    fn messages(code: &str) -> Vec<(usize, String)> {
+        parse(code, RuntimeVersion::Lua54)
+            .1
+            .into_iter()
+            .map(|d| (d.loc.line_start, format!("{} {}", d.code, d.msg)))
+            .collect()
+    }
+
+    #[test]
+    fn test_missing_end_points_at_opener() {
+        assert_eq!(
+            messages("do\n  local x = 1\n"),
+            vec![(
+                1,
+                "E006 expected `end` to close `do` block started at line 1".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_missing_end_blames_by_indentation() {
+        let code = "local function f()\n  if x then\n    print(1)\n\nend\n";
+        assert_eq!(
+            messages(code),
+            vec![(
+                2,
+                "E006 expected `end` to close `if` block started at line 2".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_assignment_in_condition() {
+        let (_, diagnostics) = parse("if a = 1 then print(a) end\n", RuntimeVersion::Lua54);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code.name(), "assignment-in-condition");
+        assert_eq!(diagnostics[0].fixes[0].replacement, "==");
+    }
+
+    #[test]
+    fn test_not_equal_operator() {
+        let (_, diagnostics) = parse("if a != b then end\n", RuntimeVersion::Lua54);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code.name(), "not-equal-operator");
+        assert_eq!(diagnostics[0].fixes[0].replacement, "~=");
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let (_, diagnostics) = parse("x += 1\ns ..= a .. b\n", RuntimeVersion::Lua54);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code.name(), "compound-assignment");
+        assert_eq!(diagnostics[0].fixes[0].replacement, "x = x + 1");
+        assert_eq!(diagnostics[1].fixes[0].replacement, "s = s .. (a .. b)");
+    }
+
+    #[test]
+    fn test_cascade_suppression() {
+        assert_eq!(
+            messages("local t = {a = 1 b = 2}\nprint(t)\n"),
+            vec![(1, "E001 expected `}` after last field".to_string())]
+        );
+    }
+}
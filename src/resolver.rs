@@ -0,0 +1,185 @@
+//! Maps `require` strings to files through the `runtime.path` templates.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use crate::config::Config;
+use crate::modules;
+use crate::workspace;
+
+#[derive(Debug, Clone, Default)]
+pub struct Resolver {
+    roots: Vec<PathBuf>,
+    templates: Vec<String>,
+}
+
+impl Resolver {
+    /// Searches `roots`, then the config's own directory and the directories
+    /// listed in `workspace.library`.
+    pub fn new(config: &Config, roots: impl IntoIterator<Item = PathBuf>) -> Self {
+        let base = config.base_dir();
+        let mut all: Vec<PathBuf> = roots.into_iter().collect();
+        all.push(base.to_path_buf());
+        all.extend(
+            config
+                .workspace
+                .library
+                .iter()
+                .map(|entry| base.join(entry))
+                .filter(|dir| dir.is_dir()),
+        );
+        let mut roots = Vec::new();
+        for root in all {
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+        Self {
+            roots,
+            templates: config
+                .runtime
+                .path
+                .split(';')
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// Finds the file of `module`. A module whose first segment names a root
+    /// directory also resolves inside that root, so `require("app.ui")` finds
+    /// `ui/init.lua` in a workspace folder named `app`.
+    pub fn resolve(&self, module: &str) -> Option<PathBuf> {
+        let name = module.replace('.', "/");
+        self.roots.iter().find_map(|root| {
+            let stripped = root
+                .file_name()
+                .and_then(|dir| name.strip_prefix(dir.to_str()?)?.strip_prefix('/'));
+            self.find(root, &name)
+                .or_else(|| stripped.and_then(|rest| self.find(root, rest)))
+        })
+    }
+
+    /// Whether `dir` is one of the directories modules are searched in.
+    pub fn is_root(&self, dir: &Path) -> bool {
+        let dir = modules::normalize(dir);
+        self.roots
+            .iter()
+            .any(|root| modules::normalize(root) == dir)
+    }
+
+    /// Names of every module that resolves to a file of the roots, sorted.
+    pub fn modules(&self) -> Vec<String> {
+        let mut ret = BTreeSet::new();
+        for root in &self.roots {
+            let Ok(files) = workspace::lua_files(root) else {
+                continue;
+            };
+            for file in files {
+                ret.extend(self.names_in(root, &file));
+            }
+        }
+        ret.into_iter().collect()
+    }
+
+    /// The name `require` loads `path` by, the shortest when there are
+    /// several, such as `ui` rather than `ui.init`.
+    pub fn module_name(&self, path: &Path) -> Option<String> {
+        let path = modules::normalize(path);
+        self.roots
+            .iter()
+            // The empty root of the default config is the current directory.
+            .flat_map(|root| self.names_in(&modules::normalize(&Path::new(".").join(root)), &path))
+            .min_by_key(String::len)
+    }
+
+    /// Module names the templates give `file` below `root`.
+    fn names_in(&self, root: &Path, file: &Path) -> Vec<String> {
+        let Ok(relative) = file.strip_prefix(root) else {
+            return Vec::new();
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        self.templates
+            .iter()
+            .filter_map(|template| {
+                let (prefix, suffix) = template.split_once('?')?;
+                let name = relative
+                    .strip_prefix(prefix.trim_start_matches("./"))?
+                    .strip_suffix(suffix)?;
+                (!name.is_empty() && !name.contains('.')).then(|| name.replace('/', "."))
+            })
+            .collect()
+    }
+
+    fn find(&self, root: &Path, name: &str) -> Option<PathBuf> {
+        self.templates
+            .iter()
+            .map(|template| root.join(template.replace('?', name)))
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver(root: &str) -> Resolver {
+        Resolver::new(&Config::default(), [PathBuf::from(root)])
+    }
+
+    #[test]
+    fn test_resolve() {
+        let resolver = resolver("tests/sample_workspace");
+        assert_eq!(
+            resolver.resolve("ui.utils"),
+            Some(PathBuf::from("tests/sample_workspace/ui/utils.lua"))
+        );
+        assert_eq!(
+            resolver.resolve("sample_workspace.ui"),
+            Some(PathBuf::from("tests/sample_workspace/ui/init.lua"))
+        );
+        assert_eq!(resolver.resolve("ui.missing"), None);
+    }
+
+    #[test]
+    fn test_resolve_custom_path() {
+        let mut config = Config::default();
+        config.runtime.path = "?/init.lua".to_string();
+        let resolver = Resolver::new(&config, [PathBuf::from("tests/sample_workspace")]);
+        assert_eq!(resolver.resolve("ui.utils"), None);
+        assert!(resolver.resolve("ui").is_some());
+    }
+
+    #[test]
+    fn test_modules() {
+        let modules = resolver("tests/modules").modules();
+        assert!(
+            ["a", "b", "main", "ui", "unused"]
+                .iter()
+                .all(|name| modules.iter().any(|m| m == name))
+        );
+        let modules = resolver("tests/sample_workspace").modules();
+        assert!(modules.iter().any(|m| m == "ui.utils"));
+        assert!(modules.iter().any(|m| m == "ui"));
+    }
+
+    #[test]
+    fn test_module_name() {
+        let resolver = resolver("tests/sample_workspace");
+        let name = |path: &str| resolver.module_name(Path::new(path));
+        assert_eq!(
+            name("tests/sample_workspace/ui/utils.lua"),
+            Some("ui.utils".to_string())
+        );
+        assert_eq!(
+            name("tests/sample_workspace/ui/init.lua"),
+            Some("ui".to_string())
+        );
+        assert_eq!(
+            name("tests/modules/ui.lua"),
+            Some("tests.modules.ui".to_string())
+        );
+    }
+}
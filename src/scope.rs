@@ -0,0 +1,913 @@
+//! Scope tree of a Lua chunk: every local declaration, every name reference and
+//! the symbol each reference resolves to. References that resolve to nothing
+//! are globals.
+
+use std::fmt;
+
+use full_moon::{
+    ast::{
+        Ast, Block, Call, Expression, Field, FunctionArgs, FunctionBody, Index, LastStmt,
+        Parameter, Prefix, Stmt, Suffix, TableConstructor, Var,
+    },
+    node::Node,
+    tokenizer::{TokenReference, TokenType},
+};
+
+use crate::diagnostic::Location;
+
+pub type ScopeId = usize;
+pub type SymbolId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    File,
+    Function,
+    Block,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub parent: Option<ScopeId>,
+    pub kind: ScopeKind,
+    /// Byte range of the source covered by the scope.
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Local,
+    LocalFunction,
+    Parameter,
+    LoopVariable,
+    /// The `self` of a function declared with `:`.
+    ImplicitSelf,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub loc: Location,
+    pub scope: ScopeId,
+    /// The symbol with the same name that was visible where this one was declared.
+    pub shadows: Option<SymbolId>,
+    /// Named fields of the table constructor the local was initialized with,
+    /// as `a` in `local M = { a = 1 }`. `None` for any other initializer.
+    pub fields: Option<Vec<Member>>,
+    /// What the local is initialized with.
+    pub value: Option<Value>,
+    /// Byte offset from which the name refers to the local: the end of its
+    /// statement for `local x = ...`, the end of the name otherwise.
+    pub visible: usize,
+}
+
+/// What the syntax of an expression tells about its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Nil,
+    Boolean,
+    Number,
+    String,
+    Table,
+    Function {
+        /// Parameter names, `...` for varargs.
+        params: Vec<String>,
+        /// Whether it is declared with `:` and takes an implicit `self`.
+        method: bool,
+    },
+}
+
+impl Value {
+    fn of(expr: &Expression) -> Option<Self> {
+        match expr {
+            Expression::BinaryOperator { lhs, binop, rhs } => {
+                match binop.token().token().to_string().as_str() {
+                    ".." => Some(Self::String),
+                    "==" | "~=" | "<" | "<=" | ">" | ">=" => Some(Self::Boolean),
+                    "and" | "or" => {
+                        Self::of(lhs).filter(|value| Self::of(rhs).as_ref() == Some(value))
+                    }
+                    _ => Some(Self::Number),
+                }
+            }
+            Expression::UnaryOperator { unop, .. } => {
+                match unop.token().token().to_string().as_str() {
+                    "not" => Some(Self::Boolean),
+                    _ => Some(Self::Number),
+                }
+            }
+            Expression::Parentheses { expression, .. } => Self::of(expression),
+            Expression::Function(function) => Some(Self::function(function.body(), false)),
+            Expression::TableConstructor(_) => Some(Self::Table),
+            Expression::Number(_) => Some(Self::Number),
+            Expression::String(_) => Some(Self::String),
+            Expression::Symbol(token) => match token.token().to_string().as_str() {
+                "nil" => Some(Self::Nil),
+                "true" | "false" => Some(Self::Boolean),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn function(body: &FunctionBody, method: bool) -> Self {
+        Self::Function {
+            params: body
+                .parameters()
+                .iter()
+                .filter_map(|parameter| match parameter {
+                    Parameter::Name(name) => Some(name.token().to_string()),
+                    Parameter::Ellipsis(_) => Some("...".to_string()),
+                    _ => None,
+                })
+                .collect(),
+            method,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Nil => f.write_str("nil"),
+            Self::Boolean => f.write_str("boolean"),
+            Self::Number => f.write_str("number"),
+            Self::String => f.write_str("string"),
+            Self::Table => f.write_str("table"),
+            Self::Function { params, .. } => write!(f, "function({})", params.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// The field directly indexed on a name, as `format` in `string.format` or
+/// `get` in `M:get()`.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub loc: Location,
+    /// Whether the field is assigned, as in `M.x = 1` or `function M.f() end`.
+    pub assigned: bool,
+    /// The value assigned to the field.
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: String,
+    pub loc: Location,
+    pub access: Access,
+    /// `None` when the name is a global.
+    pub symbol: Option<SymbolId>,
+    pub member: Option<Member>,
+    /// The value written to the name.
+    pub value: Option<Value>,
+}
+
+/// A call of the global `require` with a literal module name.
+#[derive(Debug, Clone)]
+pub struct Require {
+    pub module: String,
+    /// Location of the string literal.
+    pub loc: Location,
+    /// The local the module is bound to, as `ui` in `local ui = require("ui")`.
+    pub symbol: Option<SymbolId>,
+}
+
+/// The value a chunk returns at its top level, when it is a local name or a
+/// table constructor.
+#[derive(Debug, Clone)]
+pub struct Export {
+    /// Location of the returned name, or of the `{` of the constructor.
+    pub loc: Location,
+    pub symbol: Option<SymbolId>,
+    /// Named fields of a returned table constructor.
+    pub fields: Vec<Member>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScopeTree {
+    pub scopes: Vec<Scope>,
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+    pub requires: Vec<Require>,
+    pub export: Option<Export>,
+}
+
+impl ScopeTree {
+    pub fn build(ast: &Ast) -> Self {
+        let mut builder = Builder::default();
+        let end = ast.eof().token().end_position().bytes();
+        builder.enter(ScopeKind::File, 0, end);
+        builder.block(ast.nodes());
+        builder.export(ast.nodes());
+        builder.leave();
+        builder.tree
+    }
+
+    pub fn references_to(&self, symbol: SymbolId) -> impl Iterator<Item = &Reference> {
+        self.references
+            .iter()
+            .filter(move |r| r.symbol == Some(symbol))
+    }
+
+    pub fn is_read(&self, symbol: SymbolId) -> bool {
+        self.references_to(symbol).any(|r| r.access == Access::Read)
+    }
+
+    pub fn globals(&self) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(|r| r.symbol.is_none())
+    }
+
+    /// The innermost scope containing the byte `offset`.
+    pub fn scope_at(&self, offset: usize) -> ScopeId {
+        // Scopes are recorded parents first and siblings do not overlap, so
+        // the last one containing `offset` is the innermost.
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.start <= offset && offset <= scope.end)
+            .unwrap_or(0)
+    }
+
+    /// The locals visible at `offset`, innermost first, leaving out the ones
+    /// they shadow.
+    pub fn visible_at(&self, offset: usize) -> Vec<SymbolId> {
+        let mut scopes = Vec::new();
+        let mut scope = Some(self.scope_at(offset));
+        while let Some(id) = scope {
+            scopes.push(id);
+            scope = self.scopes[id].parent;
+        }
+        let mut ret: Vec<SymbolId> = Vec::new();
+        for (id, symbol) in self.symbols.iter().enumerate().rev() {
+            let visible = scopes.contains(&symbol.scope) && symbol.visible < offset;
+            if visible && !ret.iter().any(|r| self.symbols[*r].name == symbol.name) {
+                ret.push(id);
+            }
+        }
+        ret
+    }
+
+    /// The function, or the file, whose body `scope` belongs to.
+    pub fn function_of(&self, mut scope: ScopeId) -> ScopeId {
+        while self.scopes[scope].kind == ScopeKind::Block
+            && let Some(parent) = self.scopes[scope].parent
+        {
+            scope = parent;
+        }
+        scope
+    }
+
+    /// Whether the local `symbol`, used at `offset`, is an upvalue there: it
+    /// belongs to an enclosing function rather than the one `offset` is in.
+    pub fn is_upvalue(&self, symbol: SymbolId, offset: usize) -> bool {
+        let symbol = &self.symbols[symbol];
+        let declaration = symbol.loc.byte_start <= offset && offset <= symbol.loc.byte_end;
+        !declaration && self.function_of(self.scope_at(offset)) != self.function_of(symbol.scope)
+    }
+
+    /// Fields of the table the chunk returns. `None` when its shape cannot be
+    /// known: nothing is returned, the table comes from a call, or the local
+    /// holding it is reassigned or passed around.
+    pub fn exports(&self) -> Option<Vec<&Member>> {
+        let export = self.export.as_ref()?;
+        let Some(id) = export.symbol else {
+            return Some(export.fields.iter().collect());
+        };
+        let mut fields: Vec<&Member> = self.symbols[id].fields.as_ref()?.iter().collect();
+        for reference in self.references_to(id) {
+            match &reference.member {
+                Some(member) if member.assigned => fields.push(member),
+                Some(_) => {}
+                None if reference.loc.byte_start == export.loc.byte_start => {}
+                None => return None,
+            }
+        }
+        Some(fields)
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    tree: ScopeTree,
+    /// Scopes being walked, each with the symbols declared in it so far.
+    stack: Vec<(ScopeId, Vec<SymbolId>)>,
+}
+
+fn loc(token: &TokenReference) -> Location {
+    Location::new(token.token().start_position(), token.token().end_position())
+}
+
+fn start(token: &TokenReference) -> usize {
+    token.token().start_position().bytes()
+}
+
+fn end(token: &TokenReference) -> usize {
+    token.token().end_position().bytes()
+}
+
+impl Builder {
+    fn enter(&mut self, kind: ScopeKind, start: usize, end: usize) {
+        // The closing token of a block that a broken buffer never closes has
+        // no real position; the block then runs to the end of the file.
+        let end = match self.tree.scopes.first() {
+            Some(file) if end < start => file.end,
+            _ => end,
+        };
+        let id = self.tree.scopes.len();
+        self.tree.scopes.push(Scope {
+            parent: self.stack.last().map(|(id, _)| *id),
+            kind,
+            start,
+            end,
+        });
+        self.stack.push((id, Vec::new()));
+    }
+
+    fn leave(&mut self) {
+        self.stack.pop();
+    }
+
+    fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.stack
+            .iter()
+            .rev()
+            .flat_map(|(_, symbols)| symbols.iter().rev())
+            .find(|id| self.tree.symbols[**id].name == name)
+            .copied()
+    }
+
+    fn declare(&mut self, token: &TokenReference, kind: SymbolKind) {
+        let name = token.token().to_string();
+        let shadows = self.lookup(&name);
+        let Some((scope, symbols)) = self.stack.last_mut() else {
+            return;
+        };
+        let id = self.tree.symbols.len();
+        self.tree.symbols.push(Symbol {
+            name,
+            kind,
+            loc: loc(token),
+            scope: *scope,
+            shadows,
+            fields: None,
+            value: None,
+            visible: end(token),
+        });
+        symbols.push(id);
+    }
+
+    fn reference(&mut self, token: &TokenReference, access: Access) {
+        self.member_reference(token, access, None);
+    }
+
+    fn member_reference(
+        &mut self,
+        token: &TokenReference,
+        access: Access,
+        member: Option<&TokenReference>,
+    ) {
+        let name = token.token().to_string();
+        let symbol = self.lookup(&name);
+        self.tree.references.push(Reference {
+            name,
+            loc: loc(token),
+            access,
+            symbol,
+            member: member.map(|member| Member {
+                name: member.token().to_string(),
+                loc: loc(member),
+                assigned: false,
+                value: None,
+            }),
+            value: None,
+        });
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in block.stmts() {
+            self.stmt(stmt);
+        }
+        if let Some(LastStmt::Return(ret)) = block.last_stmt() {
+            for expr in ret.returns() {
+                self.expr(expr);
+            }
+        }
+    }
+
+    fn export(&mut self, block: &Block) {
+        let Some(LastStmt::Return(ret)) = block.last_stmt() else {
+            return;
+        };
+        if ret.returns().len() != 1 {
+            return;
+        }
+        self.tree.export = match ret.returns().iter().next() {
+            Some(Expression::Var(Var::Name(name))) => {
+                self.lookup(&name.token().to_string()).map(|id| Export {
+                    loc: loc(name),
+                    symbol: Some(id),
+                    fields: Vec::new(),
+                })
+            }
+            Some(Expression::TableConstructor(table)) => table_fields(table).map(|fields| Export {
+                loc: loc(table.braces().tokens().0),
+                symbol: None,
+                fields,
+            }),
+            _ => None,
+        };
+    }
+
+    /// Marks the member of the reference at `index` as assigned `value`.
+    fn assign_member(&mut self, index: usize, value: Option<Value>) {
+        if let Some(member) = self
+            .tree
+            .references
+            .get_mut(index)
+            .and_then(|r| r.member.as_mut())
+        {
+            member.assigned = true;
+            member.value = value;
+        }
+    }
+
+    /// Records `value` as written by the reference at `index`.
+    fn assign(&mut self, index: usize, value: Option<Value>) {
+        if let Some(reference) = self.tree.references.get_mut(index) {
+            reference.value = value;
+        }
+    }
+
+    fn scoped_block(&mut self, block: &Block, open: &TokenReference, close: &TokenReference) {
+        self.enter(ScopeKind::Block, end(open), start(close));
+        self.block(block);
+        self.leave();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Assignment(assignment) => {
+                for expr in assignment.expressions() {
+                    self.expr(expr);
+                }
+                let mut values = assignment.expressions().iter().map(Value::of);
+                for var in assignment.variables() {
+                    let index = self.tree.references.len();
+                    let value = values.next().flatten();
+                    match var {
+                        Var::Name(name) => {
+                            self.reference(name, Access::Write);
+                            self.assign(index, value);
+                        }
+                        Var::Expression(expr) => {
+                            self.var(var);
+                            let mut suffixes = expr.suffixes();
+                            if let (Some(Suffix::Index(Index::Dot { .. })), None) =
+                                (suffixes.next(), suffixes.next())
+                            {
+                                self.assign_member(index, value);
+                            }
+                        }
+                        var => self.var(var),
+                    }
+                }
+            }
+            Stmt::Do(stmt) => self.scoped_block(stmt.block(), stmt.do_token(), stmt.end_token()),
+            Stmt::FunctionCall(call) => self.indexed(call.prefix(), call.suffixes()),
+            Stmt::FunctionDeclaration(declaration) => {
+                let name = declaration.name();
+                let mut names = name.names().iter();
+                if let Some(first) = names.next() {
+                    let index = self.tree.references.len();
+                    let value = Value::function(declaration.body(), name.method_colon().is_some());
+                    match names.next().or(name.method_name()) {
+                        Some(member) => self.member_reference(first, Access::Read, Some(member)),
+                        None => {
+                            self.reference(first, Access::Write);
+                            self.assign(index, Some(value.clone()));
+                        }
+                    }
+                    // `function M.f()` and `function M:f()` define a field of
+                    // `M`; `function M.a.b()` only reads `M.a`.
+                    if name.names().len() + usize::from(name.method_name().is_some()) == 2 {
+                        self.assign_member(index, Some(value));
+                    }
+                }
+                self.function_body(declaration.body(), name.method_colon());
+            }
+            Stmt::GenericFor(stmt) => {
+                for expr in stmt.expressions() {
+                    self.expr(expr);
+                }
+                self.enter(
+                    ScopeKind::Block,
+                    end(stmt.do_token()),
+                    start(stmt.end_token()),
+                );
+                for name in stmt.names() {
+                    self.declare(name, SymbolKind::LoopVariable);
+                }
+                self.block(stmt.block());
+                self.leave();
+            }
+            Stmt::If(stmt) => {
+                // Each branch's scope ends where the next branch begins.
+                let else_ifs = stmt.else_if().map(|v| v.as_slice()).unwrap_or_default();
+                let mut closers = else_ifs
+                    .iter()
+                    .map(|else_if| else_if.else_if_token())
+                    .chain(stmt.else_token())
+                    .chain([stmt.end_token()]);
+                self.expr(stmt.condition());
+                let close = closers.next().unwrap_or(stmt.end_token());
+                self.scoped_block(stmt.block(), stmt.then_token(), close);
+                for else_if in else_ifs {
+                    self.expr(else_if.condition());
+                    let close = closers.next().unwrap_or(stmt.end_token());
+                    self.scoped_block(else_if.block(), else_if.then_token(), close);
+                }
+                if let (Some(block), Some(token)) = (stmt.else_block(), stmt.else_token()) {
+                    self.scoped_block(block, token, stmt.end_token());
+                }
+            }
+            Stmt::LocalAssignment(assignment) => {
+                let mut bound = Vec::new();
+                for (i, expr) in assignment.expressions().iter().enumerate() {
+                    let requires = self.tree.requires.len();
+                    self.expr(expr);
+                    if self.tree.requires.len() == requires + 1 && is_require_call(expr) {
+                        bound.push((requires, i));
+                    }
+                }
+                let first = self.tree.symbols.len();
+                // A statement cut short after its `=` is still being typed.
+                let visible = match assignment.equal_token() {
+                    Some(_) if assignment.expressions().is_empty() => usize::MAX,
+                    _ => assignment.end_position().map_or(0, |p| p.bytes()),
+                };
+                for name in assignment.names() {
+                    self.declare(name, SymbolKind::Local);
+                    if let Some(symbol) = self.tree.symbols.last_mut() {
+                        symbol.visible = symbol.visible.max(visible);
+                    }
+                }
+                for (i, expr) in assignment.expressions().iter().enumerate() {
+                    let Some(symbol) = self.tree.symbols.get_mut(first + i) else {
+                        break;
+                    };
+                    symbol.value = Value::of(expr);
+                    if let Expression::TableConstructor(table) = expr {
+                        symbol.fields = table_fields(table);
+                    }
+                }
+                for (require, i) in bound {
+                    if i < assignment.names().len() {
+                        self.tree.requires[require].symbol = Some(first + i);
+                    }
+                }
+            }
+            Stmt::LocalFunction(function) => {
+                let id = self.tree.symbols.len();
+                self.declare(function.name(), SymbolKind::LocalFunction);
+                if let Some(symbol) = self.tree.symbols.get_mut(id) {
+                    symbol.value = Some(Value::function(function.body(), false));
+                }
+                self.function_body(function.body(), None);
+            }
+            Stmt::NumericFor(stmt) => {
+                self.expr(stmt.start());
+                self.expr(stmt.end());
+                if let Some(step) = stmt.step() {
+                    self.expr(step);
+                }
+                self.enter(
+                    ScopeKind::Block,
+                    end(stmt.do_token()),
+                    start(stmt.end_token()),
+                );
+                self.declare(stmt.index_variable(), SymbolKind::LoopVariable);
+                self.block(stmt.block());
+                self.leave();
+            }
+            Stmt::Repeat(stmt) => {
+                // The `until` condition can see the locals of the loop body.
+                let close = stmt.until().end_position().map_or(0, |p| p.bytes());
+                self.enter(ScopeKind::Block, end(stmt.repeat_token()), close);
+                self.block(stmt.block());
+                self.expr(stmt.until());
+                self.leave();
+            }
+            Stmt::While(stmt) => {
+                self.expr(stmt.condition());
+                self.scoped_block(stmt.block(), stmt.do_token(), stmt.end_token());
+            }
+            _ => {}
+        }
+    }
+
+    fn function_body(&mut self, body: &FunctionBody, method_colon: Option<&TokenReference>) {
+        let (open, _) = body.parameters_parentheses().tokens();
+        self.enter(ScopeKind::Function, start(open), end(body.end_token()));
+        if let Some(colon) = method_colon {
+            let id = self.tree.symbols.len();
+            self.tree.symbols.push(Symbol {
+                name: "self".to_string(),
+                kind: SymbolKind::ImplicitSelf,
+                loc: loc(colon),
+                scope: self.stack.last().map_or(0, |(id, _)| *id),
+                shadows: None,
+                fields: None,
+                value: None,
+                visible: end(colon),
+            });
+            if let Some((_, symbols)) = self.stack.last_mut() {
+                symbols.push(id);
+            }
+        }
+        for parameter in body.parameters() {
+            if let Parameter::Name(name) = parameter {
+                self.declare(name, SymbolKind::Parameter);
+            }
+        }
+        self.block(body.block());
+        self.leave();
+    }
+
+    fn var(&mut self, var: &Var) {
+        match var {
+            Var::Name(name) => self.reference(name, Access::Read),
+            Var::Expression(expr) => self.indexed(expr.prefix(), expr.suffixes()),
+            _ => {}
+        }
+    }
+
+    fn indexed<'a>(&mut self, prefix: &Prefix, suffixes: impl Iterator<Item = &'a Suffix>) {
+        let mut suffixes = suffixes.peekable();
+        match prefix {
+            Prefix::Name(name) => {
+                let member = match suffixes.peek() {
+                    Some(Suffix::Index(Index::Dot { name, .. })) => Some(name),
+                    Some(Suffix::Call(Call::MethodCall(call))) => Some(call.name()),
+                    _ => None,
+                };
+                let is_require =
+                    name.token().to_string() == "require" && self.lookup("require").is_none();
+                if is_require && let Some(Suffix::Call(Call::AnonymousCall(args))) = suffixes.peek()
+                {
+                    self.require(args);
+                }
+                self.member_reference(name, Access::Read, member);
+            }
+            Prefix::Expression(expr) => self.expr(expr),
+            _ => {}
+        }
+        self.suffixes(suffixes);
+    }
+
+    fn require(&mut self, args: &FunctionArgs) {
+        let literal = match args {
+            FunctionArgs::String(token) => token,
+            FunctionArgs::Parentheses { arguments, .. } => match arguments.iter().next() {
+                Some(Expression::String(token)) if arguments.len() == 1 => token,
+                _ => return,
+            },
+            _ => return,
+        };
+        if let TokenType::StringLiteral {
+            literal: module, ..
+        } = literal.token_type()
+        {
+            self.tree.requires.push(Require {
+                module: module.to_string(),
+                loc: loc(literal),
+                symbol: None,
+            });
+        }
+    }
+
+    fn suffixes<'a>(&mut self, suffixes: impl Iterator<Item = &'a Suffix>) {
+        for suffix in suffixes {
+            match suffix {
+                Suffix::Index(Index::Brackets { expression, .. }) => self.expr(expression),
+                Suffix::Call(Call::AnonymousCall(args)) => self.args(args),
+                Suffix::Call(Call::MethodCall(call)) => self.args(call.args()),
+                _ => {}
+            }
+        }
+    }
+
+    fn args(&mut self, args: &FunctionArgs) {
+        match args {
+            FunctionArgs::Parentheses { arguments, .. } => {
+                for expr in arguments {
+                    self.expr(expr);
+                }
+            }
+            FunctionArgs::TableConstructor(table) => self.table(table),
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expression) {
+        match expr {
+            Expression::BinaryOperator { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expression::Parentheses { expression, .. }
+            | Expression::UnaryOperator { expression, .. } => self.expr(expression),
+            Expression::Function(function) => self.function_body(function.body(), None),
+            Expression::FunctionCall(call) => self.indexed(call.prefix(), call.suffixes()),
+            Expression::TableConstructor(table) => self.table(table),
+            Expression::Var(var) => self.var(var),
+            _ => {}
+        }
+    }
+
+    fn table(&mut self, table: &TableConstructor) {
+        for field in table.fields() {
+            match field {
+                Field::ExpressionKey { key, value, .. } => {
+                    self.expr(key);
+                    self.expr(value);
+                }
+                Field::NameKey { value, .. } | Field::NoKey(value) => self.expr(value),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn is_require_call(expr: &Expression) -> bool {
+    let Expression::FunctionCall(call) = expr else {
+        return false;
+    };
+    matches!(call.prefix(), Prefix::Name(name) if name.token().to_string() == "require")
+        && call.suffixes().count() == 1
+}
+
+/// Named fields of `table`, or `None` when a computed key makes its shape
+/// unknowable.
+fn table_fields(table: &TableConstructor) -> Option<Vec<Member>> {
+    let mut fields = Vec::new();
+    for field in table.fields() {
+        match field {
+            Field::NameKey { key, value, .. } => fields.push(Member {
+                name: key.token().to_string(),
+                loc: loc(key),
+                assigned: true,
+                value: Value::of(value),
+            }),
+            Field::NoKey(_) => {}
+            _ => return None,
+        }
+    }
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use full_moon::{LuaVersion, parse_fallible};
+
+    fn build(code: &str) -> ScopeTree {
+        ScopeTree::build(parse_fallible(code, LuaVersion::lua54()).ast())
+    }
+
+    #[test]
+    fn test_resolves_locals_and_globals() {
+        let tree = build("local x = y\nprint(x)\n");
+        let resolved: Vec<(&str, Option<SymbolId>, Access)> = tree
+            .references
+            .iter()
+            .map(|r| (r.name.as_str(), r.symbol, r.access))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                ("y", None, Access::Read),
+                ("print", None, Access::Read),
+                ("x", Some(0), Access::Read),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_local_is_visible_after_its_statement() {
+        let tree = build("local x = 1\ndo\n  local x = x\nend\n");
+        assert_eq!(tree.symbols[1].shadows, Some(0));
+        assert_eq!(tree.references[0].symbol, Some(0));
+    }
+
+    #[test]
+    fn test_function_scopes() {
+        let tree = build("local M = {}\nfunction M:get(key)\n  return self[key]\nend\n");
+        let kinds: Vec<SymbolKind> = tree.symbols.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SymbolKind::Local,
+                SymbolKind::ImplicitSelf,
+                SymbolKind::Parameter
+            ]
+        );
+        assert!(tree.is_read(1));
+        assert!(tree.is_read(2));
+        assert_eq!(tree.scopes[tree.symbols[2].scope].kind, ScopeKind::Function);
+    }
+
+    #[test]
+    fn test_requires() {
+        let tree =
+            build("local a = require(\"a.b\")\nlocal c = require 'c'\nlocal d = require(name)\n");
+        let modules: Vec<&str> = tree.requires.iter().map(|r| r.module.as_str()).collect();
+        assert_eq!(modules, vec!["a.b", "c"]);
+        let shadowed = build("local require = print\nrequire('x')\n");
+        assert!(shadowed.requires.is_empty());
+    }
+
+    #[test]
+    fn test_require_bindings() {
+        let tree = build("local x, ui = 1, require('ui')\nlocal n = tostring(require('n'))\n");
+        let bound: Vec<Option<&str>> = tree
+            .requires
+            .iter()
+            .map(|r| r.symbol.map(|id| tree.symbols[id].name.as_str()))
+            .collect();
+        assert_eq!(bound, vec![Some("ui"), None]);
+    }
+
+    #[test]
+    fn test_values() {
+        let tree = build(
+            "local a, b, c = 'x' .. 1, not a, #b\nlocal t = { f = function(x, ...) end }\nfunction t:m(y) end\ng = a == b\n",
+        );
+        let values: Vec<String> = tree
+            .symbols
+            .iter()
+            .filter_map(|s| Some(format!("{} {}", s.name, s.value.as_ref()?)))
+            .collect();
+        assert_eq!(values, vec!["a string", "b boolean", "c number", "t table"]);
+        let t = tree.symbols.iter().find(|s| s.name == "t").unwrap();
+        let fields = t.fields.as_ref().unwrap();
+        assert_eq!(
+            fields[0].value,
+            Some(Value::Function {
+                params: vec!["x".to_string(), "...".to_string()],
+                method: false
+            })
+        );
+        let method = tree.references.iter().find_map(|r| r.member.as_ref());
+        assert_eq!(
+            method.and_then(|m| m.value.as_ref()).map(|v| v.to_string()),
+            Some("function(y)".to_string())
+        );
+        let global = tree.globals().find(|r| r.name == "g").unwrap();
+        assert_eq!(global.value, Some(Value::Boolean));
+    }
+
+    #[test]
+    fn test_exports() {
+        let names = |code: &str| {
+            build(code)
+                .exports()
+                .map(|fields| fields.iter().map(|f| f.name.clone()).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            names(
+                "local M = { a = 1 }\nM.b = 2\nfunction M.c() end\nfunction M:d() end\nfunction M.c.e() end\nprint(M.x)\nreturn M\n"
+            ),
+            Some(vec!["a".into(), "b".into(), "c".into(), "d".into()])
+        );
+        assert_eq!(names("return { run = run, 1 }\n"), Some(vec!["run".into()]));
+        assert_eq!(names("local M = {}\nsetmetatable(M, {})\nreturn M\n"), None);
+        assert_eq!(names("local M = make()\nreturn M\n"), None);
+        assert_eq!(names("return { [key] = 1 }\n"), None);
+        assert_eq!(names("print(1)\n"), None);
+    }
+
+    #[test]
+    fn test_upvalues() {
+        let code = "local x = 1\nlocal function f()\n  do return x end\nend\nreturn x, f\n";
+        let tree = build(code);
+        let inner = code.find("return x end").unwrap() + 7;
+        let outer = code.rfind("x, f").unwrap();
+        assert!(tree.is_upvalue(0, inner));
+        assert!(!tree.is_upvalue(0, outer));
+        assert!(!tree.is_upvalue(0, code.find('x').unwrap()));
+    }
+
+    #[test]
+    fn test_repeat_until_sees_body_locals() {
+        let tree = build("repeat\n  local done = true\nuntil done\n");
+        assert!(tree.is_read(0));
+        assert_eq!(tree.globals().count(), 0);
+    }
+}
@@ -0,0 +1,303 @@
+//! Signature of the function being called at the cursor, for
+//! `textDocument/signatureHelp`. The call is found by matching brackets in
+//! the text before the cursor, so an argument list that is not closed yet
+//! still has a signature.
+
+use std::path::Path;
+
+use crate::analysis::{Analysis, KEYWORDS};
+use crate::completion::is_name_char;
+use crate::lint::Context;
+use crate::scope::Value;
+use crate::stdlib;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// The callee and its parameter list, as `M.f(x, y)`.
+    pub label: String,
+    pub params: Vec<String>,
+    /// Index of the parameter the cursor is in.
+    pub active: usize,
+    pub documentation: Option<String>,
+}
+
+/// The function named before an opening parenthesis.
+#[derive(Debug, PartialEq, Eq)]
+struct Callee<'a> {
+    owner: Option<&'a str>,
+    /// Whether it is called with `:`.
+    method: bool,
+    name: &'a str,
+}
+
+/// The name `text` ends with, and the text before it.
+fn trailing_name(text: &str) -> (&str, &str) {
+    let rest = text.trim_end_matches(is_name_char);
+    (rest, &text[rest.len()..])
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()) && !KEYWORDS.contains(&name)
+}
+
+/// Reads the callee at the end of `text`, the code before a `(`. `None`
+/// when the parenthesis is not a call of a name or a field of a name, or
+/// opens the parameters of a function declaration.
+fn callee(text: &str) -> Option<Callee<'_>> {
+    let (rest, name) = trailing_name(text.trim_end());
+    if !is_name(name) {
+        return None;
+    }
+    let (rest, owner, method) = match rest.chars().last() {
+        Some(separator @ ('.' | ':')) if !rest.ends_with("..") => {
+            let (rest, owner) = trailing_name(&rest[..rest.len() - 1]);
+            if !is_name(owner) || rest.ends_with(['.', ':']) {
+                return None;
+            }
+            (rest, Some(owner), separator == ':')
+        }
+        _ => (rest, None, false),
+    };
+    let (_, word) = trailing_name(rest.trim_end());
+    (word != "function").then_some(Callee {
+        owner,
+        method,
+        name,
+    })
+}
+
+/// If a long bracket such as `[[` or `[==[` opens at `start`, the byte
+/// after its closing bracket, or the end of `code` when it is not closed.
+fn long_bracket(code: &str, start: usize) -> Option<usize> {
+    let rest = code[start..].strip_prefix('[')?;
+    let level = rest.len() - rest.trim_start_matches('=').len();
+    if !rest[level..].starts_with('[') {
+        return None;
+    }
+    let close = format!("]{}]", "=".repeat(level));
+    Some(
+        code[start..]
+            .find(&close)
+            .map_or(code.len(), |end| start + end + close.len()),
+    )
+}
+
+/// The brackets left open at the end of `code`, outermost first, each with
+/// its byte position and the number of commas directly inside it. Strings
+/// and comments are skipped.
+fn open_brackets(code: &str) -> Vec<(u8, usize, usize)> {
+    let bytes = code.as_bytes();
+    let mut stack: Vec<(u8, usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = long_bracket(code, i + 2)
+                    .unwrap_or_else(|| code[i..].find('\n').map_or(bytes.len(), |end| i + end));
+                continue;
+            }
+            b'[' => {
+                if let Some(end) = long_bracket(code, i) {
+                    i = end;
+                    continue;
+                }
+                stack.push((b'[', i, 0));
+            }
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote && bytes[i] != b'\n' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            bracket @ (b'(' | b'{') => stack.push((bracket, i, 0)),
+            b')' | b'}' | b']' => {
+                stack.pop();
+            }
+            b',' => {
+                if let Some((_, _, commas)) = stack.last_mut() {
+                    *commas += 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    stack
+}
+
+/// The signature of the call whose arguments the byte `offset` is in.
+pub fn signature_at(
+    analysis: &Analysis,
+    offset: usize,
+    path: Option<&Path>,
+    context: &Context,
+) -> Option<Signature> {
+    let code = &analysis.code[..offset.min(analysis.code.len())];
+    for (bracket, position, commas) in open_brackets(code).into_iter().rev() {
+        if bracket != b'(' {
+            continue;
+        }
+        let Some(callee) = callee(&code[..position]) else {
+            continue;
+        };
+        let (mut params, method, documentation) =
+            resolve(analysis, &callee, offset, path, context)?;
+        match (callee.method, method) {
+            (true, false) if !params.is_empty() => {
+                params.remove(0);
+            }
+            (false, true) => params.insert(0, "self".to_string()),
+            _ => {}
+        }
+        let name = match callee.owner {
+            Some(owner) => format!(
+                "{owner}{}{}",
+                if callee.method { ":" } else { "." },
+                callee.name
+            ),
+            None => callee.name.to_string(),
+        };
+        let active = match params.last() {
+            Some(last) if last == "..." => commas.min(params.len() - 1),
+            _ => commas,
+        };
+        return Some(Signature {
+            label: format!("{name}({})", params.join(", ")),
+            params,
+            active,
+            documentation,
+        });
+    }
+    None
+}
+
+/// Parameters of the function `callee` names, whether it takes an implicit
+/// `self`, and its description.
+fn resolve(
+    analysis: &Analysis,
+    callee: &Callee,
+    offset: usize,
+    path: Option<&Path>,
+    context: &Context,
+) -> Option<(Vec<String>, bool, Option<String>)> {
+    let tree = &analysis.tree;
+    let local = |name: &str| {
+        tree.visible_at(offset)
+            .into_iter()
+            .find(|id| tree.symbols[*id].name == name)
+    };
+    let builtin = |name: &str| {
+        let definition = stdlib::lookup(name)?;
+        let params = definition
+            .params?
+            .split(", ")
+            .filter(|param| !param.is_empty())
+            .map(str::to_string)
+            .collect();
+        Some((params, false, Some(format!("{}.", definition.doc))))
+    };
+    let function = |value: Option<&Value>| match value {
+        Some(Value::Function { params, method }) => Some((params.clone(), *method, None)),
+        _ => None,
+    };
+    match callee.owner {
+        None => match local(callee.name) {
+            Some(id) => function(tree.symbols[id].value.as_ref()),
+            None => match analysis
+                .global_definitions(callee.name, path, context)
+                .first()
+            {
+                Some((_, value)) => function(*value),
+                None => builtin(callee.name),
+            },
+        },
+        Some(owner) => match local(owner) {
+            Some(id) if tree.symbols[id].value == Some(Value::String) => {
+                builtin(&format!("string.{}", callee.name))
+            }
+            Some(id) => {
+                let (_, field) = analysis.field(id, callee.name, context)?;
+                function(field.value.as_ref())
+            }
+            None => {
+                let assigned = tree
+                    .globals()
+                    .filter(|r| r.name == owner)
+                    .filter_map(|r| r.member.as_ref())
+                    .find(|m| m.assigned && m.name == callee.name);
+                match assigned {
+                    Some(member) => function(member.value.as_ref()),
+                    None => builtin(&format!("{owner}.{}", callee.name)),
+                }
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RuntimeVersion;
+
+    fn signature(code: &str) -> Option<(String, usize)> {
+        let analysis = Analysis::new(code, RuntimeVersion::Lua54);
+        signature_at(&analysis, code.len(), None, &Context::default())
+            .map(|signature| (signature.label, signature.active))
+    }
+
+    #[test]
+    fn test_callee() {
+        assert_eq!(
+            callee("x = M.f "),
+            Some(Callee {
+                owner: Some("M"),
+                method: false,
+                name: "f"
+            })
+        );
+        assert_eq!(callee("local function f"), None);
+        assert_eq!(callee("function M:f"), None);
+        assert_eq!(callee("x = a.b.c"), None);
+        assert_eq!(callee("if"), None);
+        assert_eq!(callee("x = 1 + "), None);
+    }
+
+    #[test]
+    fn test_signatures() {
+        let code = "local function add(a, b) return a + b end\nlocal t = { f = function(...) end }\nfunction t:m(x, y) end\nlocal s = 'x'\n";
+        assert_eq!(
+            signature(&format!("{code}print(add(1, ")),
+            Some(("add(a, b)".into(), 1))
+        );
+        assert_eq!(
+            signature(&format!("{code}add((1 + 2), {{ 3, 4 }}, ")),
+            Some(("add(a, b)".into(), 2))
+        );
+        assert_eq!(
+            signature(&format!("{code}t.f(1, 2, ")),
+            Some(("t.f(...)".into(), 0))
+        );
+        assert_eq!(
+            signature(&format!("{code}t:m(")),
+            Some(("t:m(x, y)".into(), 0))
+        );
+        assert_eq!(
+            signature(&format!("{code}t.m(")),
+            Some(("t.m(self, x, y)".into(), 0))
+        );
+        assert_eq!(
+            signature(&format!("{code}s:rep(3, ")),
+            Some(("s:rep(n, sep?)".into(), 1))
+        );
+        assert_eq!(
+            signature("string.format(\"%s, %s\", "),
+            Some(("string.format(formatstring, ...)".into(), 1))
+        );
+        assert_eq!(
+            signature("print(\"(\" --[[ ( ]] , "),
+            Some(("print(...)".into(), 0))
+        );
+        assert_eq!(signature("print(x) "), None);
+        assert_eq!(signature("local function f(a, "), None);
+    }
+}
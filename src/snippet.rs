@@ -0,0 +1,273 @@
+use std::{fmt::Write, path::Path};
+
+use crate::diagnostic::{LuascanDiagnostic, Severity};
+
+const BOLD: &str = "\x1b[1m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+const BOLD_BLUE: &str = "\x1b[1;34m";
+const BOLD_CYAN: &str = "\x1b[1;36m";
+const RESET: &str = "\x1b[0m";
+/// Columns a tab is rendered with.
+pub const TAB_WIDTH: usize = 4;
+/// Spans longer than this many lines are shown with their middle elided.
+const MAX_SPAN_LINES: usize = 6;
+
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Renders `diagnostic` rustc-style: a header, the `path:line:col` it points
+/// at and the affected source lines underlined with carets.
+pub fn render(path: &Path, source: &str, diagnostic: &LuascanDiagnostic, color: bool) -> String {
+    let painter = Painter { color };
+    let accent = match diagnostic.severity {
+        Severity::Error => BOLD_RED,
+        Severity::Warning => BOLD_YELLOW,
+        Severity::Info | Severity::Hint => BOLD_CYAN,
+    };
+    let loc = &diagnostic.loc;
+    let lines: Vec<&str> = source.lines().collect();
+    let line_start = loc.line_start.max(1);
+    let line_end = loc.line_end.max(line_start);
+    let width = line_end.to_string().len();
+    let pad = " ".repeat(width);
+    let bar = painter.paint(BOLD_BLUE, "|");
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}{}",
+        painter.paint(
+            accent,
+            &format!("{}[{}]", diagnostic.severity, diagnostic.code)
+        ),
+        painter.paint(BOLD, &format!(": {}", diagnostic.msg))
+    );
+    let _ = writeln!(
+        out,
+        "{}{} {}:{}:{}",
+        pad,
+        painter.paint(BOLD_BLUE, "-->"),
+        path.display(),
+        loc.line_start,
+        loc.col_start
+    );
+    let _ = writeln!(out, "{pad} {bar}");
+
+    let numbers: Vec<usize> = (line_start..=line_end).collect();
+    let shown: Vec<Option<usize>> = if numbers.len() > MAX_SPAN_LINES {
+        let head = numbers[..MAX_SPAN_LINES / 2].iter().copied().map(Some);
+        let tail = numbers[numbers.len() - 2..].iter().copied().map(Some);
+        head.chain([None]).chain(tail).collect()
+    } else {
+        numbers.into_iter().map(Some).collect()
+    };
+    for number in shown {
+        let Some(number) = number else {
+            let _ = writeln!(out, "{}", painter.paint(BOLD_BLUE, "..."));
+            continue;
+        };
+        let text = lines.get(number - 1).copied().unwrap_or("");
+        let label = painter.paint(BOLD_BLUE, &format!("{number:>width$}"));
+        let _ = writeln!(out, "{label} {bar} {}", expand_tabs(text));
+
+        let len = text.chars().count();
+        let from = if number == line_start {
+            loc.col_start.saturating_sub(1)
+        } else {
+            0
+        };
+        let to = if number == line_end {
+            loc.col_end.saturating_sub(1)
+        } else {
+            len
+        };
+        let to = if number == line_end {
+            to.max(from + 1)
+        } else {
+            to
+        };
+        if to <= from {
+            continue;
+        }
+        let indent = display_width(text, from);
+        let carets = display_width(text, to).max(indent + 1) - indent;
+        let _ = writeln!(
+            out,
+            "{pad} {bar} {}{}",
+            " ".repeat(indent),
+            painter.paint(accent, &"^".repeat(carets))
+        );
+    }
+    for related in &diagnostic.related {
+        let _ = writeln!(
+            out,
+            "{pad} {} {}: {} ({}:{}:{})",
+            painter.paint(BOLD_BLUE, "="),
+            painter.paint(BOLD, "note"),
+            related.msg,
+            related.path.as_deref().unwrap_or(path).display(),
+            related.loc.line_start,
+            related.loc.col_start
+        );
+    }
+    for fix in &diagnostic.fixes {
+        let _ = writeln!(
+            out,
+            "{pad} {} {}: {}",
+            painter.paint(BOLD_BLUE, "="),
+            painter.paint(BOLD, "help"),
+            fix.msg
+        );
+    }
+    out
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// Terminal columns taken by the first `chars` characters of `text`. A tab
+/// takes `TAB_WIDTH` columns and an East Asian wide character two; positions
+/// past the end of `text` take one each.
+fn display_width(text: &str, chars: usize) -> usize {
+    let width: usize = text.chars().take(chars).map(char_width).sum();
+    width + chars.saturating_sub(text.chars().count())
+}
+
+fn char_width(c: char) -> usize {
+    // The blocks of wide and fullwidth characters in common use: Hangul,
+    // CJK ideographs and punctuation, kana, fullwidth forms and emoji.
+    const WIDE: &[(u32, u32)] = &[
+        (0x1100, 0x115F),
+        (0x2E80, 0x303E),
+        (0x3041, 0x33FF),
+        (0x3400, 0x4DBF),
+        (0x4E00, 0x9FFF),
+        (0xA000, 0xA4CF),
+        (0xAC00, 0xD7A3),
+        (0xF900, 0xFAFF),
+        (0xFE30, 0xFE4F),
+        (0xFF00, 0xFF60),
+        (0xFFE0, 0xFFE6),
+        (0x1F300, 0x1F64F),
+        (0x1F900, 0x1F9FF),
+        (0x20000, 0x3FFFD),
+    ];
+    match c {
+        '\t' => TAB_WIDTH,
+        c if WIDE
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(&(c as u32))) =>
+        {
+            2
+        }
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::{Location, RuleCode};
+    use pretty_assertions::assert_eq;
+
+    fn diagnostic(lines: (usize, usize), cols: (usize, usize)) -> LuascanDiagnostic {
+        LuascanDiagnostic::new(
+            RuleCode::UnexpectedToken,
+            Location {
+                line_start: lines.0,
+                line_end: lines.1,
+                col_start: cols.0,
+                col_end: cols.1,
+                byte_start: 0,
+                byte_end: 0,
+            },
+            "unexpected token",
+        )
+    }
+
+    #[test]
+    fn test_render_single_line() {
+        let source = "local x = 1\n\tprint(x y)\n";
+        let rendered = render(
+            Path::new("a.lua"),
+            source,
+            &diagnostic((2, 2), (10, 11)),
+            false,
+        );
+        assert_eq!(
+            rendered,
+            "error[E001]: unexpected token\n --> a.lua:2:10\n  |\n2 |     print(x y)\n  |             ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_after_wide_characters() {
+        let source = "s = \"日本\" x\n";
+        let rendered = render(
+            Path::new("a.lua"),
+            source,
+            &diagnostic((1, 1), (10, 11)),
+            false,
+        );
+        assert_eq!(
+            rendered,
+            "error[E001]: unexpected token\n --> a.lua:1:10\n  |\n1 | s = \"日本\" x\n  |            ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_multi_line() {
+        let source = "local s = [[abc\ndef\nghi";
+        let rendered = render(
+            Path::new("a.lua"),
+            source,
+            &diagnostic((1, 3), (11, 4)),
+            false,
+        );
+        assert_eq!(
+            rendered,
+            concat!(
+                "error[E001]: unexpected token\n",
+                " --> a.lua:1:11\n",
+                "  |\n",
+                "1 | local s = [[abc\n",
+                "  |           ^^^^^\n",
+                "2 | def\n",
+                "  | ^^^\n",
+                "3 | ghi\n",
+                "  | ^^^\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_related_and_fix() {
+        let d = diagnostic((1, 1), (1, 2))
+            .with_related(diagnostic((3, 3), (1, 2)).loc, "block opened here")
+            .with_fix(diagnostic((1, 1), (1, 2)).loc, "~=", "use `~=`");
+        let rendered = render(Path::new("a.lua"), "a != b", &d, false);
+        assert!(
+            rendered.ends_with("  = note: block opened here (a.lua:3:1)\n  = help: use `~=`\n")
+        );
+    }
+
+    #[test]
+    fn test_render_at_eof_with_color() {
+        let rendered = render(Path::new("a.lua"), "f(", &diagnostic((1, 1), (3, 3)), true);
+        assert!(rendered.starts_with("\x1b[1;31merror[E001]\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+    }
+}
@@ -0,0 +1,1180 @@
+//! Built-in definitions of the standard library of each supported runtime.
+
+use crate::config::RuntimeVersion;
+
+const V51: u8 = 1;
+const V52: u8 = 1 << 1;
+const V53: u8 = 1 << 2;
+const V54: u8 = 1 << 3;
+const JIT: u8 = 1 << 4;
+const ALL: u8 = V51 | V52 | V53 | V54 | JIT;
+
+const VERSIONS: [(RuntimeVersion, u8); 5] = [
+    (RuntimeVersion::Lua51, V51),
+    (RuntimeVersion::Lua52, V52),
+    (RuntimeVersion::Lua53, V53),
+    (RuntimeVersion::Lua54, V54),
+    (RuntimeVersion::Luajit, JIT),
+];
+
+/// A global or library member such as `print`, `string` or `string.format`.
+#[derive(Debug)]
+pub struct Definition {
+    /// Dotted path of the definition.
+    pub name: &'static str,
+    /// Parameter list of a function; `None` for other values. Optional
+    /// parameters end with `?`.
+    pub params: Option<&'static str>,
+    versions: u8,
+    /// One-line description.
+    pub doc: &'static str,
+}
+
+impl Definition {
+    pub fn is_available(&self, version: RuntimeVersion) -> bool {
+        self.versions & mask(version) != 0
+    }
+
+    /// Whether an earlier Lua release provides the definition and `version`
+    /// dropped it. LuaJIT follows Lua 5.1 and drops nothing.
+    pub fn is_removed_in(&self, version: RuntimeVersion) -> bool {
+        version != RuntimeVersion::Luajit
+            && !self.is_available(version)
+            && VERSIONS
+                .iter()
+                .take_while(|(v, _)| *v != version)
+                .any(|(_, bit)| self.versions & bit != 0)
+    }
+
+    /// Runtimes providing the definition, in release order.
+    pub fn versions(&self) -> impl Iterator<Item = RuntimeVersion> + '_ {
+        VERSIONS
+            .iter()
+            .filter(|(_, bit)| self.versions & bit != 0)
+            .map(|(version, _)| *version)
+    }
+}
+
+const fn function(
+    name: &'static str,
+    params: &'static str,
+    versions: u8,
+    doc: &'static str,
+) -> Definition {
+    Definition {
+        name,
+        params: Some(params),
+        versions,
+        doc,
+    }
+}
+
+const fn value(name: &'static str, versions: u8, doc: &'static str) -> Definition {
+    Definition {
+        name,
+        params: None,
+        versions,
+        doc,
+    }
+}
+
+/// LuaJIT's `ffi` has members but no global: it is only reachable through
+/// `require("ffi")`.
+static DEFINITIONS: &[Definition] = &[
+    function(
+        "assert",
+        "v, message?",
+        ALL,
+        "Raises an error if `v` is false or nil; otherwise returns all its arguments",
+    ),
+    function(
+        "collectgarbage",
+        "opt?, arg?",
+        ALL,
+        "Controls the garbage collector",
+    ),
+    function(
+        "dofile",
+        "filename?",
+        ALL,
+        "Runs the file `filename` and returns its results",
+    ),
+    function(
+        "error",
+        "message, level?",
+        ALL,
+        "Raises an error with `message` as the error object",
+    ),
+    function(
+        "gcinfo",
+        "",
+        V51 | JIT,
+        "Returns the memory in use, in kilobytes",
+    ),
+    function(
+        "getfenv",
+        "f?",
+        V51 | JIT,
+        "Returns the environment of a function",
+    ),
+    function(
+        "getmetatable",
+        "object",
+        ALL,
+        "Returns the metatable of `object`",
+    ),
+    function(
+        "ipairs",
+        "t",
+        ALL,
+        "Iterates over the integer keys 1, 2, ... of `t` until the first nil",
+    ),
+    function(
+        "load",
+        "chunk, chunkname?, mode?, env?",
+        ALL,
+        "Loads a chunk from a string or function without running it",
+    ),
+    function(
+        "loadfile",
+        "filename?, mode?, env?",
+        ALL,
+        "Loads a chunk from a file without running it",
+    ),
+    function(
+        "loadstring",
+        "string, chunkname?",
+        V51 | JIT,
+        "Loads a chunk from a string without running it",
+    ),
+    function(
+        "module",
+        "name, ...",
+        V51 | JIT,
+        "Creates a module table and sets it as the environment of the chunk",
+    ),
+    function(
+        "newproxy",
+        "proxy?",
+        V51 | JIT,
+        "Creates a zero-size userdata, optionally with a metatable",
+    ),
+    function(
+        "next",
+        "table, index?",
+        ALL,
+        "Returns the key and value following `index` in `table`",
+    ),
+    function(
+        "pairs",
+        "t",
+        ALL,
+        "Iterates over all key-value pairs of `t`",
+    ),
+    function(
+        "pcall",
+        "f, ...",
+        ALL,
+        "Calls `f` in protected mode, returning false and the error on failure",
+    ),
+    function(
+        "print",
+        "...",
+        ALL,
+        "Writes its arguments to stdout, converted with `tostring`",
+    ),
+    function(
+        "rawequal",
+        "v1, v2",
+        ALL,
+        "Compares two values without invoking `__eq`",
+    ),
+    function(
+        "rawget",
+        "table, index",
+        ALL,
+        "Reads `table[index]` without invoking `__index`",
+    ),
+    function(
+        "rawlen",
+        "v",
+        V52 | V53 | V54,
+        "Returns the length of a table or string without invoking `__len`",
+    ),
+    function(
+        "rawset",
+        "table, index, value",
+        ALL,
+        "Sets `table[index]` without invoking `__newindex`",
+    ),
+    function(
+        "require",
+        "modname",
+        ALL,
+        "Loads the module `modname` and returns its value",
+    ),
+    function(
+        "select",
+        "index, ...",
+        ALL,
+        "Returns the arguments after `index`, or their count for `\"#\"`",
+    ),
+    function(
+        "setfenv",
+        "f, table",
+        V51 | JIT,
+        "Sets the environment of a function",
+    ),
+    function(
+        "setmetatable",
+        "table, metatable",
+        ALL,
+        "Sets the metatable of `table` and returns it",
+    ),
+    function(
+        "tonumber",
+        "e, base?",
+        ALL,
+        "Converts `e` to a number, in the given base",
+    ),
+    function(
+        "tostring",
+        "v",
+        ALL,
+        "Converts `v` to a string, using `__tostring` when present",
+    ),
+    function("type", "v", ALL, "Returns the type of `v` as a string"),
+    function(
+        "unpack",
+        "list, i?, j?",
+        V51 | JIT,
+        "Returns the elements `list[i]` to `list[j]`",
+    ),
+    function(
+        "warn",
+        "msg1, ...",
+        V54,
+        "Emits a warning built from its arguments",
+    ),
+    function(
+        "xpcall",
+        "f, msgh, ...",
+        ALL,
+        "Calls `f` in protected mode with `msgh` as the message handler",
+    ),
+    value(
+        "_ENV",
+        V52 | V53 | V54,
+        "The environment of the current chunk",
+    ),
+    value("_G", ALL, "The global environment"),
+    value(
+        "_VERSION",
+        ALL,
+        "The running Lua version, such as `\"Lua 5.4\"`",
+    ),
+    value("bit", JIT, "LuaJIT's bitwise operations library"),
+    value("bit32", V52, "Bitwise operations on 32-bit integers"),
+    value("coroutine", ALL, "Coroutine manipulation library"),
+    value("debug", ALL, "Debug interface library"),
+    value("io", ALL, "Input and output library"),
+    value("jit", JIT, "LuaJIT compiler control library"),
+    value("math", ALL, "Mathematical functions library"),
+    value("os", ALL, "Operating system facilities library"),
+    value("package", ALL, "Module loading library"),
+    value("string", ALL, "String manipulation library"),
+    value("table", ALL, "Table manipulation library"),
+    value("utf8", V53 | V54, "UTF-8 support library"),
+    function(
+        "bit.arshift",
+        "x, n",
+        JIT,
+        "Arithmetic right shift of `x` by `n` bits",
+    ),
+    function("bit.band", "x1, ...", JIT, "Bitwise and of its arguments"),
+    function("bit.bnot", "x", JIT, "Bitwise not of `x`"),
+    function("bit.bor", "x1, ...", JIT, "Bitwise or of its arguments"),
+    function("bit.bswap", "x", JIT, "Swaps the bytes of `x`"),
+    function(
+        "bit.bxor",
+        "x1, ...",
+        JIT,
+        "Bitwise exclusive or of its arguments",
+    ),
+    function(
+        "bit.lshift",
+        "x, n",
+        JIT,
+        "Logical left shift of `x` by `n` bits",
+    ),
+    function("bit.rol", "x, n", JIT, "Rotates `x` left by `n` bits"),
+    function("bit.ror", "x, n", JIT, "Rotates `x` right by `n` bits"),
+    function(
+        "bit.rshift",
+        "x, n",
+        JIT,
+        "Logical right shift of `x` by `n` bits",
+    ),
+    function("bit.tobit", "x", JIT, "Normalizes `x` to a 32-bit integer"),
+    function(
+        "bit.tohex",
+        "x, n?",
+        JIT,
+        "Converts `x` to a hex string of `n` digits",
+    ),
+    function(
+        "bit32.arshift",
+        "x, disp",
+        V52,
+        "Arithmetic shift of `x` by `disp` bits",
+    ),
+    function("bit32.band", "...", V52, "Bitwise and of its arguments"),
+    function("bit32.bnot", "x", V52, "Bitwise not of `x`"),
+    function("bit32.bor", "...", V52, "Bitwise or of its arguments"),
+    function(
+        "bit32.btest",
+        "...",
+        V52,
+        "Whether the bitwise and of its arguments is not zero",
+    ),
+    function(
+        "bit32.bxor",
+        "...",
+        V52,
+        "Bitwise exclusive or of its arguments",
+    ),
+    function(
+        "bit32.extract",
+        "n, field, width?",
+        V52,
+        "Extracts `width` bits of `n` starting at `field`",
+    ),
+    function(
+        "bit32.lrotate",
+        "x, disp",
+        V52,
+        "Rotates `x` left by `disp` bits",
+    ),
+    function(
+        "bit32.lshift",
+        "x, disp",
+        V52,
+        "Shifts `x` left by `disp` bits",
+    ),
+    function(
+        "bit32.replace",
+        "n, v, field, width?",
+        V52,
+        "Replaces `width` bits of `n` starting at `field` with `v`",
+    ),
+    function(
+        "bit32.rrotate",
+        "x, disp",
+        V52,
+        "Rotates `x` right by `disp` bits",
+    ),
+    function(
+        "bit32.rshift",
+        "x, disp",
+        V52,
+        "Shifts `x` right by `disp` bits",
+    ),
+    function(
+        "coroutine.close",
+        "co",
+        V54,
+        "Closes the suspended or dead coroutine `co`",
+    ),
+    function(
+        "coroutine.create",
+        "f",
+        ALL,
+        "Creates a coroutine running `f`",
+    ),
+    function(
+        "coroutine.isyieldable",
+        "co?",
+        V53 | V54 | JIT,
+        "Whether the running coroutine can yield",
+    ),
+    function(
+        "coroutine.resume",
+        "co, ...",
+        ALL,
+        "Starts or continues the coroutine `co`",
+    ),
+    function(
+        "coroutine.running",
+        "",
+        ALL,
+        "Returns the running coroutine",
+    ),
+    function(
+        "coroutine.status",
+        "co",
+        ALL,
+        "Returns the status of `co` as a string",
+    ),
+    function(
+        "coroutine.wrap",
+        "f",
+        ALL,
+        "Creates a coroutine running `f` and returns a function resuming it",
+    ),
+    function(
+        "coroutine.yield",
+        "...",
+        ALL,
+        "Suspends the running coroutine, passing its arguments to `resume`",
+    ),
+    function(
+        "debug.debug",
+        "",
+        ALL,
+        "Enters an interactive debugging prompt",
+    ),
+    function(
+        "debug.getfenv",
+        "o",
+        V51 | JIT,
+        "Returns the environment of `o`",
+    ),
+    function(
+        "debug.gethook",
+        "thread?",
+        ALL,
+        "Returns the current hook settings of a thread",
+    ),
+    function(
+        "debug.getinfo",
+        "thread?, f, what?",
+        ALL,
+        "Returns a table with information about a function or stack level",
+    ),
+    function(
+        "debug.getlocal",
+        "thread?, f, local",
+        ALL,
+        "Returns the name and value of a local variable at a stack level",
+    ),
+    function(
+        "debug.getmetatable",
+        "value",
+        ALL,
+        "Returns the metatable of a value, ignoring `__metatable`",
+    ),
+    function("debug.getregistry", "", ALL, "Returns the registry table"),
+    function(
+        "debug.getupvalue",
+        "f, up",
+        ALL,
+        "Returns the name and value of an upvalue of `f`",
+    ),
+    function(
+        "debug.getuservalue",
+        "u, n?",
+        V52 | V53 | V54,
+        "Returns the user value associated with a userdata",
+    ),
+    function(
+        "debug.setcstacklimit",
+        "limit",
+        V54,
+        "Sets the limit of the C stack",
+    ),
+    function(
+        "debug.setfenv",
+        "object, table",
+        V51 | JIT,
+        "Sets the environment of `object`",
+    ),
+    function(
+        "debug.sethook",
+        "thread?, hook, mask, count?",
+        ALL,
+        "Sets a debug hook function",
+    ),
+    function(
+        "debug.setlocal",
+        "thread?, level, local, value",
+        ALL,
+        "Sets the value of a local variable at a stack level",
+    ),
+    function(
+        "debug.setmetatable",
+        "value, table",
+        ALL,
+        "Sets the metatable of a value, of any type",
+    ),
+    function(
+        "debug.setupvalue",
+        "f, up, value",
+        ALL,
+        "Sets the value of an upvalue of `f`",
+    ),
+    function(
+        "debug.setuservalue",
+        "udata, value, n?",
+        V52 | V53 | V54,
+        "Sets the user value associated with a userdata",
+    ),
+    function(
+        "debug.traceback",
+        "thread?, message?, level?",
+        ALL,
+        "Returns a traceback of the call stack",
+    ),
+    function(
+        "debug.upvalueid",
+        "f, n",
+        V52 | V53 | V54 | JIT,
+        "Returns a unique identifier of an upvalue",
+    ),
+    function(
+        "debug.upvaluejoin",
+        "f1, n1, f2, n2",
+        V52 | V53 | V54 | JIT,
+        "Makes an upvalue of one closure refer to an upvalue of another",
+    ),
+    value("ffi.C", JIT, "Namespace of the default C library"),
+    function(
+        "ffi.abi",
+        "param",
+        JIT,
+        "Whether the target ABI has the given property",
+    ),
+    function(
+        "ffi.alignof",
+        "ct",
+        JIT,
+        "Returns the alignment of a C type",
+    ),
+    value("ffi.arch", JIT, "The target architecture name"),
+    function("ffi.cast", "ct, init", JIT, "Converts a value to a C type"),
+    function("ffi.cdef", "def", JIT, "Adds C declarations"),
+    function(
+        "ffi.copy",
+        "dst, src, len?",
+        JIT,
+        "Copies bytes between memory areas",
+    ),
+    function(
+        "ffi.errno",
+        "newerr?",
+        JIT,
+        "Returns and optionally sets the C `errno`",
+    ),
+    function(
+        "ffi.fill",
+        "dst, len, c?",
+        JIT,
+        "Fills a memory area with a byte value",
+    ),
+    function(
+        "ffi.gc",
+        "cdata, finalizer",
+        JIT,
+        "Attaches a finalizer to a cdata object",
+    ),
+    function(
+        "ffi.istype",
+        "ct, obj",
+        JIT,
+        "Whether a cdata object has the given C type",
+    ),
+    function(
+        "ffi.load",
+        "name, global?",
+        JIT,
+        "Loads a dynamic C library",
+    ),
+    function(
+        "ffi.metatype",
+        "ct, metatable",
+        JIT,
+        "Creates a C type with a metatable",
+    ),
+    function(
+        "ffi.new",
+        "ct, nelem?, init?",
+        JIT,
+        "Creates a cdata object of a C type",
+    ),
+    function(
+        "ffi.offsetof",
+        "ct, field",
+        JIT,
+        "Returns the offset of a field in a C struct",
+    ),
+    value("ffi.os", JIT, "The target operating system name"),
+    function(
+        "ffi.sizeof",
+        "ct, nelem?",
+        JIT,
+        "Returns the size of a C type",
+    ),
+    function(
+        "ffi.string",
+        "ptr, len?",
+        JIT,
+        "Creates a Lua string from C memory",
+    ),
+    function(
+        "ffi.typeof",
+        "ct",
+        JIT,
+        "Creates a ctype object for a C type",
+    ),
+    function(
+        "io.close",
+        "file?",
+        ALL,
+        "Closes `file`, or the default output file",
+    ),
+    function("io.flush", "", ALL, "Flushes the default output file"),
+    function(
+        "io.input",
+        "file?",
+        ALL,
+        "Sets or returns the default input file",
+    ),
+    function(
+        "io.lines",
+        "filename?, ...",
+        ALL,
+        "Iterates over the lines of a file",
+    ),
+    function(
+        "io.open",
+        "filename, mode?",
+        ALL,
+        "Opens a file in the given mode",
+    ),
+    function(
+        "io.output",
+        "file?",
+        ALL,
+        "Sets or returns the default output file",
+    ),
+    function(
+        "io.popen",
+        "prog, mode?",
+        ALL,
+        "Runs a program and returns a file connected to it",
+    ),
+    function("io.read", "...", ALL, "Reads from the default input file"),
+    value("io.stderr", ALL, "The standard error file"),
+    value("io.stdin", ALL, "The standard input file"),
+    value("io.stdout", ALL, "The standard output file"),
+    function(
+        "io.tmpfile",
+        "",
+        ALL,
+        "Opens a temporary file in update mode",
+    ),
+    function(
+        "io.type",
+        "obj",
+        ALL,
+        "Whether `obj` is an open file, a closed file, or neither",
+    ),
+    function(
+        "io.write",
+        "...",
+        ALL,
+        "Writes its arguments to the default output file",
+    ),
+    value("jit.arch", JIT, "The target architecture name"),
+    function(
+        "jit.attach",
+        "f, event?",
+        JIT,
+        "Attaches a handler to a compiler event",
+    ),
+    function("jit.flush", "...", JIT, "Flushes the compiled code cache"),
+    function("jit.off", "...", JIT, "Disables the JIT compiler"),
+    function("jit.on", "...", JIT, "Enables the JIT compiler"),
+    value("jit.opt", JIT, "JIT compiler optimization control"),
+    value("jit.os", JIT, "The target operating system name"),
+    function(
+        "jit.status",
+        "",
+        JIT,
+        "Returns whether the compiler is on and its flags",
+    ),
+    value("jit.version", JIT, "The LuaJIT version string"),
+    value("jit.version_num", JIT, "The LuaJIT version as a number"),
+    function("math.abs", "x", ALL, "Absolute value of `x`"),
+    function("math.acos", "x", ALL, "Arc cosine of `x`, in radians"),
+    function("math.asin", "x", ALL, "Arc sine of `x`, in radians"),
+    function(
+        "math.atan",
+        "y, x?",
+        ALL,
+        "Arc tangent of `y / x`, in radians",
+    ),
+    function(
+        "math.atan2",
+        "y, x",
+        V51 | V52 | JIT,
+        "Arc tangent of `y / x`, in radians",
+    ),
+    function("math.ceil", "x", ALL, "Smallest integer not less than `x`"),
+    function("math.cos", "x", ALL, "Cosine of `x`, in radians"),
+    function(
+        "math.cosh",
+        "x",
+        V51 | V52 | JIT,
+        "Hyperbolic cosine of `x`",
+    ),
+    function("math.deg", "x", ALL, "Converts `x` from radians to degrees"),
+    function("math.exp", "x", ALL, "The value e^x"),
+    function(
+        "math.floor",
+        "x",
+        ALL,
+        "Largest integer not greater than `x`",
+    ),
+    function(
+        "math.fmod",
+        "x, y",
+        ALL,
+        "Remainder of `x / y` rounded towards zero",
+    ),
+    function(
+        "math.frexp",
+        "x",
+        V51 | V52 | JIT,
+        "Splits `x` into a mantissa and an exponent",
+    ),
+    value("math.huge", ALL, "A value larger than any other number"),
+    function("math.ldexp", "m, e", V51 | V52 | JIT, "The value m * 2^e"),
+    function(
+        "math.log",
+        "x, base?",
+        ALL,
+        "Logarithm of `x` in the given base",
+    ),
+    function("math.log10", "x", V51 | JIT, "Base-10 logarithm of `x`"),
+    function("math.max", "x, ...", ALL, "Largest of its arguments"),
+    value("math.maxinteger", V53 | V54, "The largest integer"),
+    function("math.min", "x, ...", ALL, "Smallest of its arguments"),
+    value("math.mininteger", V53 | V54, "The smallest integer"),
+    function(
+        "math.modf",
+        "x",
+        ALL,
+        "Integral and fractional parts of `x`",
+    ),
+    value("math.pi", ALL, "The value of pi"),
+    function("math.pow", "x, y", V51 | V52 | JIT, "The value x^y"),
+    function("math.rad", "x", ALL, "Converts `x` from degrees to radians"),
+    function(
+        "math.random",
+        "m?, n?",
+        ALL,
+        "Pseudo-random number in a range",
+    ),
+    function(
+        "math.randomseed",
+        "x?",
+        ALL,
+        "Seeds the pseudo-random generator",
+    ),
+    function("math.sin", "x", ALL, "Sine of `x`, in radians"),
+    function("math.sinh", "x", V51 | V52 | JIT, "Hyperbolic sine of `x`"),
+    function("math.sqrt", "x", ALL, "Square root of `x`"),
+    function("math.tan", "x", ALL, "Tangent of `x`, in radians"),
+    function(
+        "math.tanh",
+        "x",
+        V51 | V52 | JIT,
+        "Hyperbolic tangent of `x`",
+    ),
+    function(
+        "math.tointeger",
+        "x",
+        V53 | V54,
+        "Converts `x` to an integer, or returns fail",
+    ),
+    function(
+        "math.type",
+        "x",
+        V53 | V54,
+        "Returns `\"integer\"`, `\"float\"` or fail",
+    ),
+    function(
+        "math.ult",
+        "m, n",
+        V53 | V54,
+        "Whether `m` is below `n` when compared as unsigned integers",
+    ),
+    function(
+        "os.clock",
+        "",
+        ALL,
+        "CPU time used by the program, in seconds",
+    ),
+    function("os.date", "format?, time?", ALL, "Formats a date and time"),
+    function(
+        "os.difftime",
+        "t2, t1",
+        ALL,
+        "Difference in seconds between two times",
+    ),
+    function("os.execute", "command?", ALL, "Runs a shell command"),
+    function("os.exit", "code?, close?", ALL, "Terminates the program"),
+    function(
+        "os.getenv",
+        "varname",
+        ALL,
+        "Returns the value of an environment variable",
+    ),
+    function(
+        "os.remove",
+        "filename",
+        ALL,
+        "Deletes a file or empty directory",
+    ),
+    function(
+        "os.rename",
+        "oldname, newname",
+        ALL,
+        "Renames a file or directory",
+    ),
+    function(
+        "os.setlocale",
+        "locale, category?",
+        ALL,
+        "Sets the current locale",
+    ),
+    function(
+        "os.time",
+        "table?",
+        ALL,
+        "Current time, or the time of a date table",
+    ),
+    function(
+        "os.tmpname",
+        "",
+        ALL,
+        "Returns a name usable for a temporary file",
+    ),
+    value(
+        "package.config",
+        ALL,
+        "Compile-time configuration of packages",
+    ),
+    value("package.cpath", ALL, "Search path for C loaders"),
+    value("package.loaded", ALL, "Table of already loaded modules"),
+    value("package.loaders", V51 | JIT, "Searchers used by `require`"),
+    function(
+        "package.loadlib",
+        "libname, funcname",
+        ALL,
+        "Links a C library and returns one of its functions",
+    ),
+    value("package.path", ALL, "Search path for Lua loaders"),
+    value(
+        "package.preload",
+        ALL,
+        "Table of loaders for specific modules",
+    ),
+    value(
+        "package.searchers",
+        V52 | V53 | V54,
+        "Searchers used by `require`",
+    ),
+    function(
+        "package.searchpath",
+        "name, path, sep?, rep?",
+        V52 | V53 | V54 | JIT,
+        "Searches `path` for the file of module `name`",
+    ),
+    function(
+        "package.seeall",
+        "module",
+        V51 | JIT,
+        "Makes a module see the global environment",
+    ),
+    function(
+        "string.byte",
+        "s, i?, j?",
+        ALL,
+        "Internal numeric codes of the characters `s[i]` to `s[j]`",
+    ),
+    function(
+        "string.char",
+        "...",
+        ALL,
+        "String made of the characters with the given codes",
+    ),
+    function(
+        "string.dump",
+        "function, strip?",
+        ALL,
+        "Binary representation of a function",
+    ),
+    function(
+        "string.find",
+        "s, pattern, init?, plain?",
+        ALL,
+        "Finds the first match of `pattern` in `s`",
+    ),
+    function(
+        "string.format",
+        "formatstring, ...",
+        ALL,
+        "Formats its arguments following a printf-style format string",
+    ),
+    function(
+        "string.gmatch",
+        "s, pattern, init?",
+        ALL,
+        "Iterates over the matches of `pattern` in `s`",
+    ),
+    function(
+        "string.gsub",
+        "s, pattern, repl, n?",
+        ALL,
+        "Replaces the matches of `pattern` in `s`",
+    ),
+    function("string.len", "s", ALL, "Length of `s`"),
+    function("string.lower", "s", ALL, "Copy of `s` in lowercase"),
+    function(
+        "string.match",
+        "s, pattern, init?",
+        ALL,
+        "Captures of the first match of `pattern` in `s`",
+    ),
+    function(
+        "string.pack",
+        "fmt, v1, ...",
+        V53 | V54,
+        "Packs values into a binary string",
+    ),
+    function(
+        "string.packsize",
+        "fmt",
+        V53 | V54,
+        "Size of a string packed with the given format",
+    ),
+    function(
+        "string.rep",
+        "s, n, sep?",
+        ALL,
+        "Concatenation of `n` copies of `s`",
+    ),
+    function("string.reverse", "s", ALL, "`s` reversed"),
+    function(
+        "string.sub",
+        "s, i, j?",
+        ALL,
+        "Substring of `s` from `i` to `j`",
+    ),
+    function(
+        "string.unpack",
+        "fmt, s, pos?",
+        V53 | V54,
+        "Unpacks values from a binary string",
+    ),
+    function("string.upper", "s", ALL, "Copy of `s` in uppercase"),
+    function(
+        "table.concat",
+        "list, sep?, i?, j?",
+        ALL,
+        "Concatenates the string elements of `list`",
+    ),
+    function("table.getn", "table", V51 | JIT, "Length of `table`"),
+    function(
+        "table.insert",
+        "list, pos?, value",
+        ALL,
+        "Inserts `value` into `list`",
+    ),
+    function(
+        "table.maxn",
+        "table",
+        V51 | JIT,
+        "Largest positive numeric index of `table`",
+    ),
+    function(
+        "table.move",
+        "a1, f, e, t, a2?",
+        V53 | V54 | JIT,
+        "Moves elements from one table to another",
+    ),
+    function(
+        "table.pack",
+        "...",
+        V52 | V53 | V54,
+        "Packs its arguments into a table with a field `n`",
+    ),
+    function(
+        "table.remove",
+        "list, pos?",
+        ALL,
+        "Removes and returns an element of `list`",
+    ),
+    function("table.sort", "list, comp?", ALL, "Sorts `list` in place"),
+    function(
+        "table.unpack",
+        "list, i?, j?",
+        V52 | V53 | V54,
+        "Returns the elements `list[i]` to `list[j]`",
+    ),
+    function(
+        "utf8.char",
+        "...",
+        V53 | V54,
+        "UTF-8 string made of the given code points",
+    ),
+    value(
+        "utf8.charpattern",
+        V53 | V54,
+        "Pattern matching exactly one UTF-8 character",
+    ),
+    function(
+        "utf8.codepoint",
+        "s, i?, j?, lax?",
+        V53 | V54,
+        "Code points of the characters of `s` from `i` to `j`",
+    ),
+    function(
+        "utf8.codes",
+        "s, lax?",
+        V53 | V54,
+        "Iterates over the code points of `s`",
+    ),
+    function(
+        "utf8.len",
+        "s, i?, j?, lax?",
+        V53 | V54,
+        "Number of UTF-8 characters in `s`",
+    ),
+    function(
+        "utf8.offset",
+        "s, n, i?",
+        V53 | V54,
+        "Byte position of the `n`-th character of `s`",
+    ),
+];
+
+fn mask(version: RuntimeVersion) -> u8 {
+    VERSIONS
+        .iter()
+        .find(|(v, _)| *v == version)
+        .map_or(0, |(_, bit)| *bit)
+}
+
+/// Finds a definition by dotted path, whatever runtime provides it.
+pub fn lookup(name: &str) -> Option<&'static Definition> {
+    DEFINITIONS.iter().find(|d| d.name == name)
+}
+
+/// Names of the globals available in `version`.
+pub fn globals(version: RuntimeVersion) -> Vec<&'static str> {
+    DEFINITIONS
+        .iter()
+        .filter(|d| !d.name.contains('.') && d.is_available(version))
+        .map(|d| d.name)
+        .collect()
+}
+
+/// Members of the library `library` (such as `string`) available in `version`.
+pub fn members(
+    library: &str,
+    version: RuntimeVersion,
+) -> impl Iterator<Item = &'static Definition> + '_ {
+    DEFINITIONS.iter().filter(move |d| {
+        d.is_available(version)
+            && d.name
+                .strip_prefix(library)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// Modules `require` can load without a file: the standard libraries and the
+/// extension modules bundled with LuaJIT.
+pub fn is_builtin_module(name: &str, version: RuntimeVersion) -> bool {
+    const LUAJIT_MODULES: &[&str] = &[
+        "ffi",
+        "jit.opt",
+        "jit.util",
+        "jit.profile",
+        "string.buffer",
+        "table.new",
+        "table.clear",
+    ];
+    let is_library = lookup(name).is_some_and(|d| {
+        !d.name.contains('.') && d.params.is_none() && d.name != "_G" && d.is_available(version)
+    });
+    is_library || (version == RuntimeVersion::Luajit && LUAJIT_MODULES.contains(&name))
+}
+
+/// What to use instead of a definition in runtimes that lack it.
+pub fn replacement(name: &str, version: RuntimeVersion) -> Option<&'static str> {
+    let replacement = match name {
+        "unpack" => "table.unpack",
+        "table.unpack" => "unpack",
+        "loadstring" => "load",
+        "math.pow" => "the `^` operator",
+        "setfenv" | "getfenv" => "`_ENV`",
+        "table.getn" => "the `#` operator",
+        "bit32" => "the bitwise operators",
+        _ => return None,
+    };
+    match lookup(replacement) {
+        Some(d) if !d.is_available(version) => None,
+        _ => Some(replacement),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_globals_per_version() {
+        let lua51 = globals(RuntimeVersion::Lua51);
+        let lua54 = globals(RuntimeVersion::Lua54);
+        let luajit = globals(RuntimeVersion::Luajit);
+        assert!(lua51.contains(&"setfenv") && !lua54.contains(&"setfenv"));
+        assert!(lua54.contains(&"utf8") && !lua51.contains(&"utf8"));
+        assert!(luajit.contains(&"bit") && !luajit.contains(&"ffi"));
+        assert!(globals(RuntimeVersion::Lua52).contains(&"bit32"));
+    }
+
+    #[test]
+    fn test_builtin_modules() {
+        assert!(is_builtin_module("string", RuntimeVersion::Lua51));
+        assert!(is_builtin_module("ffi", RuntimeVersion::Luajit));
+        assert!(!is_builtin_module("ffi", RuntimeVersion::Lua54));
+        assert!(!is_builtin_module("print", RuntimeVersion::Lua54));
+    }
+
+    #[test]
+    fn test_members() {
+        let names: Vec<&str> = members("table", RuntimeVersion::Lua54)
+            .map(|d| d.name)
+            .collect();
+        assert!(names.contains(&"table.unpack") && !names.contains(&"table.getn"));
+        assert!(members("ffi", RuntimeVersion::Luajit).any(|d| d.name == "ffi.cdef"));
+        assert_eq!(members("ffi", RuntimeVersion::Lua54).count(), 0);
+    }
+
+    #[test]
+    fn test_definitions_are_unique() {
+        for d in DEFINITIONS {
+            assert!(lookup(d.name).is_some_and(|found| std::ptr::eq(found, d)));
+            if let Some((library, _)) = d.name.split_once('.') {
+                assert!(
+                    library == "ffi" || lookup(library).is_some(),
+                    "{} belongs to an unknown library",
+                    d.name
+                );
+            }
+            assert!(d.versions().next().is_some());
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Inline suppression comments:
+//!
+//! ```lua
+//! -- luascan: ignore-file W002
+//! -- luascan: ignore-next-line unused-local
+//! -- luascan: disable W001, W002
+//! -- luascan: enable
+//! ```
+//!
+//! Rules are named by code or name; without any, every rule but the syntax
+//! errors is suppressed. Comments that suppress nothing or cannot be
+//! understood are reported so they don't pile up, and a comment naming a rule
+//! that does not exist is ignored rather than widened to every rule.
+
+use full_moon::{
+    LuaVersion,
+    tokenizer::{Lexer, LexerResult, Token, TokenType},
+};
+
+use crate::config::RuntimeVersion;
+use crate::diagnostic::{Location, LuascanDiagnostic, RuleCode, Tag};
+use crate::parser;
+
+const PREFIX: &str = "luascan:";
+
+#[derive(Debug)]
+struct Suppression {
+    directive: &'static str,
+    /// Suppressed rules; empty means every rule but the syntax errors.
+    codes: Vec<RuleCode>,
+    loc: Location,
+    /// Inclusive range of suppressed lines.
+    lines: (usize, usize),
+    used: bool,
+}
+
+impl Suppression {
+    fn covers(&self, d: &LuascanDiagnostic) -> bool {
+        (self.lines.0..=self.lines.1).contains(&d.loc.line_start)
+            && !d.code.is_syntax_error()
+            && (self.codes.is_empty() || self.codes.contains(&d.code))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Suppressions {
+    items: Vec<Suppression>,
+    problems: Vec<LuascanDiagnostic>,
+}
+
+impl Suppressions {
+    pub fn parse(code: &str, version: RuntimeVersion) -> Self {
+        let mut ret = Self::default();
+        for comment in comments(code, parser::lua_version(version)) {
+            ret.directive(&comment);
+        }
+        ret
+    }
+
+    fn directive(&mut self, comment: &Token) {
+        let TokenType::SingleLineComment { comment: text } = comment.token_type() else {
+            return;
+        };
+        let Some(rest) = text.trim().strip_prefix(PREFIX) else {
+            return;
+        };
+        let loc = Location::new(comment.start_position(), comment.end_position());
+        let mut words = rest
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty());
+        let directive = words.next().unwrap_or_default();
+        let mut codes = Vec::new();
+        let mut valid = true;
+        for word in words {
+            let msg = match RuleCode::lookup(word) {
+                Some(code) if !code.is_syntax_error() => {
+                    codes.push(code);
+                    continue;
+                }
+                Some(code) => format!("syntax error `{code}` cannot be suppressed"),
+                None => format!("unknown rule `{word}` in suppression comment"),
+            };
+            self.problems.push(LuascanDiagnostic::new(
+                RuleCode::UnknownSuppression,
+                loc.clone(),
+                msg,
+            ));
+            valid = false;
+        }
+        // Dropping only the bad words could leave no rule, which would widen
+        // the comment to every rule; the whole comment is ignored instead.
+        if !valid {
+            return;
+        }
+        let line = loc.line_start;
+        let (directive, lines) = match directive {
+            "ignore-next-line" => ("ignore-next-line", (line + 1, line + 1)),
+            "ignore-file" => ("ignore-file", (1, usize::MAX)),
+            "disable" => ("disable", (line, usize::MAX)),
+            "enable" => {
+                self.enable(&codes, loc);
+                return;
+            }
+            _ => {
+                self.problems.push(LuascanDiagnostic::new(
+                    RuleCode::UnknownSuppression,
+                    loc,
+                    format!(
+                        "unknown suppression `{directive}`; expected `ignore-next-line`, `ignore-file`, `disable` or `enable`"
+                    ),
+                ));
+                return;
+            }
+        };
+        self.items.push(Suppression {
+            directive,
+            codes,
+            loc,
+            lines,
+            used: false,
+        });
+    }
+
+    /// Closes the open `disable` regions that share a rule with `codes`, or
+    /// all of them when `codes` is empty.
+    fn enable(&mut self, codes: &[RuleCode], loc: Location) {
+        let mut closed = false;
+        for item in &mut self.items {
+            let shares_code = codes.is_empty()
+                || item.codes.is_empty()
+                || item.codes.iter().any(|code| codes.contains(code));
+            if item.directive == "disable" && item.lines.1 == usize::MAX && shares_code {
+                item.lines.1 = loc.line_start;
+                closed = true;
+            }
+        }
+        if !closed {
+            self.problems.push(
+                LuascanDiagnostic::new(
+                    RuleCode::UnusedSuppression,
+                    loc.clone(),
+                    "`enable` without a matching `disable`",
+                )
+                .with_tag(Tag::Unnecessary)
+                .with_fix(loc, "", "remove the comment"),
+            );
+        }
+    }
+
+    /// Adds the malformed directives to the syntax errors of code that did
+    /// not parse. Those cannot be suppressed, and without the other checks
+    /// there is no telling whether a suppression is used.
+    pub fn apply_to_syntax_errors(
+        mut self,
+        mut diagnostics: Vec<LuascanDiagnostic>,
+    ) -> Vec<LuascanDiagnostic> {
+        diagnostics.append(&mut self.problems);
+        diagnostics.sort_by_key(|d| d.loc.byte_start);
+        diagnostics
+    }
+
+    /// Drops suppressed diagnostics and reports suppressions that matched
+    /// nothing.
+    pub fn apply(mut self, diagnostics: Vec<LuascanDiagnostic>) -> Vec<LuascanDiagnostic> {
+        let mut ret: Vec<LuascanDiagnostic> = diagnostics
+            .into_iter()
+            .filter(|d| {
+                let mut suppressed = false;
+                for item in self.items.iter_mut().filter(|item| item.covers(d)) {
+                    item.used = true;
+                    suppressed = true;
+                }
+                !suppressed
+            })
+            .collect();
+        ret.extend(self.items.iter().filter(|item| !item.used).map(|item| {
+            LuascanDiagnostic::new(
+                RuleCode::UnusedSuppression,
+                item.loc.clone(),
+                format!("`{}` suppresses no diagnostics", item.directive),
+            )
+            .with_tag(Tag::Unnecessary)
+            .with_fix(item.loc.clone(), "", "remove the comment")
+        }));
+        ret.append(&mut self.problems);
+        ret.sort_by_key(|d| d.loc.byte_start);
+        ret
+    }
+}
+
+fn comments(code: &str, version: LuaVersion) -> Vec<Token> {
+    let mut lexer = Lexer::new(code, version);
+    let mut ret = Vec::new();
+    while let Some(result) = lexer.consume() {
+        let token = match result {
+            LexerResult::Ok(token) | LexerResult::Recovered(token, _) => token,
+            LexerResult::Fatal(_) => continue,
+        };
+        ret.extend(
+            token
+                .leading_trivia()
+                .chain(token.trailing_trivia())
+                .filter(|t| matches!(t.token_type(), TokenType::SingleLineComment { .. }))
+                .cloned(),
+        );
+        if matches!(token.token_type(), TokenType::Eof) {
+            break;
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Config, RuntimeConfig, RuntimeVersion};
+    use crate::lint::{self, Context};
+    use pretty_assertions::assert_eq;
+
+    fn check(code: &str) -> Vec<(usize, String)> {
+        let config = Config {
+            runtime: RuntimeConfig {
+                version: RuntimeVersion::Lua54,
+                ..RuntimeConfig::default()
+            },
+            ..Config::default()
+        };
+        lint::check(code, None, &config, &Context::default())
+            .into_iter()
+            .map(|d| (d.loc.line_start, format!("{} {}", d.code, d.msg)))
+            .collect()
+    }
+
+    #[test]
+    fn test_ignore_next_line() {
+        let code = "-- luascan: ignore-next-line W001\nprint(a)\nprint(b)\n";
+        assert_eq!(
+            check(code),
+            vec![(3, "W001 undefined global `b`".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_ignore_file() {
+        let code = "print(a)\n-- luascan: ignore-file undefined-global\nprint(b)\n";
+        assert_eq!(check(code), vec![]);
+    }
+
+    #[test]
+    fn test_disable_enable_region() {
+        let code = "-- luascan: disable W001, W002\nx = a\n-- luascan: enable\nprint(b)\n";
+        assert_eq!(
+            check(code),
+            vec![(4, "W001 undefined global `b`".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_unknown_rule_does_not_widen_suppression() {
+        let code = "-- luascan: ignore-file W999\nprint(a)\nlocal x = 1\n";
+        let reported: Vec<String> = check(code)
+            .into_iter()
+            .map(|(_, msg)| msg[..4].to_string())
+            .collect();
+        assert_eq!(reported, vec!["W008", "W001", "W003"]);
+        let code = "-- luascan: ignore-file W999\nlocal x = \n";
+        assert!(check(code).iter().any(|(_, msg)| msg.starts_with("E001")));
+
+        let code = "-- luascan: disable W001\n-- luascan: enable W99\nprint(a)\n";
+        assert_eq!(
+            check(code),
+            vec![(
+                2,
+                "W008 unknown rule `W99` in suppression comment".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors_are_not_suppressed() {
+        let code = "-- luascan: ignore-file\nlocal x = \n";
+        let codes: Vec<String> = check(code)
+            .into_iter()
+            .map(|(_, msg)| msg[..4].to_string())
+            .collect();
+        assert_eq!(codes, vec!["E001"]);
+        let code = "-- luascan: ignore-next-line W999\nlocal x = \n";
+        assert_eq!(
+            check(code)[0].1,
+            "W008 unknown rule `W999` in suppression comment"
+        );
+        let code = "-- luascan: ignore-next-line E001\nlocal x = \n";
+        assert_eq!(
+            check(code)[0].1,
+            "W008 syntax error `E001` cannot be suppressed"
+        );
+    }
+
+    #[test]
+    fn test_unused_and_unknown_suppressions() {
+        let code = "-- luascan: ignore-next-line\nlocal x = 1\nprint(x)\n-- luascan: ignore-file W999\n-- luascan: ignroe-file\n";
+        assert_eq!(
+            check(code),
+            vec![
+                (
+                    1,
+                    "W007 `ignore-next-line` suppresses no diagnostics".to_string()
+                ),
+                (
+                    4,
+                    "W008 unknown rule `W999` in suppression comment".to_string()
+                ),
+                (5, "W008 unknown suppression `ignroe-file`; expected `ignore-next-line`, `ignore-file`, `disable` or `enable`".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Index of the symbols of every file of the workspace and its library, for
+//! `workspace/symbol`. Files are outlined once when they are indexed and
+//! again when they change, so a query only ranks what is already known.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::config::RuntimeVersion;
+use crate::diagnostic::Location;
+use crate::modules;
+use crate::outline::{self, OutlineItem, OutlineKind};
+
+/// Most symbols a query returns.
+const MAX_RESULTS: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct IndexedSymbol {
+    /// The name qualified by its module and tables, as `ui.initialize_ui`.
+    pub name: String,
+    pub kind: OutlineKind,
+    /// The qualified name of the function or module it is declared in.
+    pub container: Option<String>,
+    /// The name at the definition.
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    files: HashMap<PathBuf, Vec<IndexedSymbol>>,
+}
+
+impl SymbolIndex {
+    /// Replaces the symbols of `path` with those of `code`. `module` is the
+    /// name the file is required by, which qualifies the table it returns.
+    pub fn update(
+        &mut self,
+        path: &Path,
+        code: &str,
+        version: RuntimeVersion,
+        module: Option<&str>,
+    ) {
+        let mut symbols = Vec::new();
+        for item in outline::outline(code, version) {
+            let (name, container) = match (item.kind, module) {
+                (OutlineKind::Module, Some(module)) => (module.to_string(), None),
+                _ => (item.name.clone(), module.map(str::to_string)),
+            };
+            collect(item, name, container, &mut symbols);
+        }
+        self.files.insert(modules::normalize(path), symbols);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.files.remove(&modules::normalize(path));
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(&modules::normalize(path))
+    }
+
+    /// Symbols whose name fuzzily matches `query`, best first. An empty
+    /// query matches every symbol.
+    pub fn search(&self, query: &str) -> Vec<(&Path, &IndexedSymbol)> {
+        let mut matches: Vec<(i64, &Path, &IndexedSymbol)> = self
+            .files
+            .iter()
+            .flat_map(|(path, symbols)| symbols.iter().map(move |symbol| (path, symbol)))
+            .filter_map(|(path, symbol)| {
+                Some((score(query, &symbol.name)?, path.as_path(), symbol))
+            })
+            .collect();
+        matches.sort_by(|(a, a_path, a_symbol), (b, b_path, b_symbol)| {
+            b.cmp(a)
+                .then(a_symbol.name.len().cmp(&b_symbol.name.len()))
+                .then(a_symbol.name.cmp(&b_symbol.name))
+                .then(a_path.cmp(b_path))
+                .then(a_symbol.loc.byte_start.cmp(&b_symbol.loc.byte_start))
+        });
+        matches
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, path, symbol)| (path, symbol))
+            .collect()
+    }
+}
+
+/// Adds `item`, named `name`, and its children to `symbols`. Fields and
+/// methods of a table are qualified by it; what a function declares is not,
+/// and only has the function as its container.
+fn collect(
+    item: OutlineItem,
+    name: String,
+    container: Option<String>,
+    symbols: &mut Vec<IndexedSymbol>,
+) {
+    let qualifies = matches!(item.kind, OutlineKind::Table | OutlineKind::Module);
+    for child in item.children {
+        let (child_name, child_container) = if qualifies {
+            let separator = if child.kind == OutlineKind::Method {
+                ":"
+            } else {
+                "."
+            };
+            (
+                format!("{name}{separator}{}", child.name),
+                container.clone(),
+            )
+        } else {
+            (child.name.clone(), Some(name.clone()))
+        };
+        collect(child, child_name, child_container, symbols);
+    }
+    symbols.push(IndexedSymbol {
+        name,
+        kind: item.kind,
+        container,
+        loc: item.selection,
+    });
+}
+
+/// How well `query` matches `candidate`, ignoring case, or `None` when its
+/// characters do not all appear in order. Matches at the start of a word
+/// and runs of consecutive characters score higher; gaps score lower.
+fn score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let chars: Vec<char> = candidate.chars().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let bonus = |j: usize| match j.checked_sub(1).map(|prev| chars[prev]) {
+        None => 8,
+        Some('.' | ':' | '_') => 8,
+        Some(prev) if prev.is_lowercase() && chars[j].is_uppercase() => 6,
+        _ => 0,
+    };
+    let matches = |i: usize, j: usize| chars[j].to_lowercase().eq(std::iter::once(query[i]));
+    // `row[j]` is the best score of the query so far with its last
+    // character matched at `j`. Starting at a word is not charged for the
+    // text before it, so `ui.render` matches `render` as well as `render_all`.
+    let mut row: Vec<Option<i64>> = (0..chars.len())
+        .map(|j| {
+            matches(0, j).then(|| match bonus(j) {
+                0 => -(j as i64),
+                bonus => bonus,
+            })
+        })
+        .collect();
+    for i in 1..query.len() {
+        let mut next = vec![None; chars.len()];
+        // Best of `row[k] + k` for `k < j - 1`, to charge the gap `j - k - 1`.
+        let mut before: Option<i64> = None;
+        for j in 1..chars.len() {
+            if j >= 2
+                && let Some(score) = row[j - 2]
+            {
+                before = before.max(Some(score + (j - 2) as i64));
+            }
+            if !matches(i, j) {
+                continue;
+            }
+            let consecutive = row[j - 1].map(|score| score + 5);
+            let gap = before.map(|score| score - (j - 1) as i64);
+            next[j] = consecutive.max(gap).map(|score| score + bonus(j));
+        }
+        row = next;
+    }
+    row.into_iter().flatten().max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn names(index: &SymbolIndex, query: &str) -> Vec<String> {
+        index
+            .search(query)
+            .into_iter()
+            .map(|(_, symbol)| symbol.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_score() {
+        assert!(score("iui", "initialize_ui").is_some());
+        assert_eq!(score("uii", "initialize_ui"), None);
+        assert!(score("ui.init", "ui.initialize_ui") > score("ui.init", "utils.print_in_tab"));
+        assert!(score("render", "render") > score("render", "surrender"));
+        assert!(score("nu", "newUser") > score("nu", "menu"));
+        assert_eq!(score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut index = SymbolIndex::default();
+        let ui = Path::new("tests/modules/ui.lua");
+        index.update(
+            ui,
+            &fs::read_to_string(ui).unwrap(),
+            RuntimeVersion::Lua54,
+            Some("ui"),
+        );
+        let code = "local Class = {}\nfunction Class:new()\n  local function helper() end\nend\nfunction render_all() end\n";
+        index.update(Path::new("class.lua"), code, RuntimeVersion::Lua54, None);
+        let mut all = names(&index, "");
+        all.sort();
+        assert_eq!(
+            all,
+            vec![
+                "Class",
+                "Class:new",
+                "helper",
+                "render_all",
+                "ui",
+                "ui.initialize_ui",
+                "ui.render",
+                "ui.theme",
+            ]
+        );
+        assert_eq!(names(&index, "render"), vec!["ui.render", "render_all"]);
+        assert_eq!(names(&index, "ui.init")[0], "ui.initialize_ui");
+        let (_, helper) = index.search("helper")[0];
+        assert_eq!(helper.container.as_deref(), Some("Class:new"));
+
+        index.update(
+            ui,
+            "local M = {}\nreturn M\n",
+            RuntimeVersion::Lua54,
+            Some("ui"),
+        );
+        assert!(
+            names(&index, "render")
+                .iter()
+                .all(|name| name == "render_all")
+        );
+        index.remove(ui);
+        assert!(!index.contains(ui));
+        assert!(
+            names(&index, "ui")
+                .iter()
+                .all(|name| !name.starts_with("ui"))
+        );
+    }
+}
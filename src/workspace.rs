@@ -1,6 +1,323 @@
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use glob::glob;
+use tower_lsp::lsp_types::{TextDocumentContentChangeEvent, Url};
+use tracing::{Level, event};
+
+use crate::{
+    config::Config, document::Document, error::LuascanError, library::Library,
+    line_index::PositionEncoding, modules::ModuleGraph, resolver::Resolver, symbols::SymbolIndex,
+};
+
+/// Project model shared by the language server: the open workspace folders,
+/// the `.luascan.toml` files discovered below them, the indexed sources, the
+/// module graph linking them and the symbols they define.
+#[derive(Debug, Default)]
+pub struct Workspace {
+    folders: Vec<PathBuf>,
+    fallback: Config,
+    configs: HashMap<PathBuf, Config>,
+    /// Loaded `workspace.library` of each config, keyed by its base dir.
+    libraries: HashMap<PathBuf, Arc<Library>>,
+    documents: HashMap<Url, Document>,
+    modules: Arc<ModuleGraph>,
+    symbols: SymbolIndex,
+}
+
+impl Workspace {
+    pub fn new(fallback: Config) -> Self {
+        Self {
+            fallback,
+            ..Self::default()
+        }
+    }
+
+    pub fn folders(&self) -> &[PathBuf] {
+        &self.folders
+    }
+
+    pub fn add_folder(&mut self, folder: PathBuf) {
+        if !self.folders.contains(&folder) {
+            self.folders.push(folder);
+        }
+    }
+
+    /// Forgets `folder` together with its configs and documents, and returns
+    /// the URIs of the documents that were dropped.
+    pub fn remove_folder(&mut self, folder: &Path) -> Vec<Url> {
+        self.folders.retain(|f| f != folder);
+        let still_covered = |path: &Path| self.folders.iter().any(|f| path.starts_with(f));
+        let removed_configs: Vec<PathBuf> = self
+            .configs
+            .keys()
+            .filter(|dir| dir.starts_with(folder) && !still_covered(dir))
+            .cloned()
+            .collect();
+        for dir in removed_configs {
+            self.configs.remove(&dir);
+            self.libraries.remove(&dir);
+        }
+        let removed_docs: Vec<Url> = self
+            .documents
+            .keys()
+            .filter(|uri| {
+                uri.to_file_path()
+                    .is_ok_and(|path| path.starts_with(folder) && !still_covered(&path))
+            })
+            .cloned()
+            .collect();
+        let modules = Arc::make_mut(&mut self.modules);
+        modules.unmark_indexed(folder);
+        for uri in &removed_docs {
+            self.documents.remove(uri);
+            if let Ok(path) = uri.to_file_path() {
+                modules.remove(&path);
+                self.symbols.remove(&path);
+            }
+        }
+        removed_docs
+    }
+
+    /// Resolves the config of the closest ancestor `.luascan.toml` of `path`,
+    /// falling back to the config the server was started with.
+    pub fn config_for(&mut self, path: &Path) -> Result<Config> {
+        let Some(config_path) = Config::find_config_path(path) else {
+            return Ok(self.fallback.clone());
+        };
+        let dir = config_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        if let Some(config) = self.configs.get(&dir) {
+            return Ok(config.clone());
+        }
+        let config = Config::load_from_file(&config_path)?;
+        event!(Level::INFO, "load config {:?}", &config_path);
+        self.configs.insert(dir, config.clone());
+        Ok(config)
+    }
+
+    /// Drops the cached config of `config_path` so the next lookup reads it again.
+    pub fn invalidate_config(&mut self, config_path: &Path) {
+        if let Some(dir) = config_path.parent() {
+            self.configs.remove(dir);
+            self.libraries.remove(dir);
+        }
+    }
+
+    /// Returns the `workspace.library` of `config`, loading it on first use.
+    pub fn library_for(&mut self, config: &Config) -> Arc<Library> {
+        let dir = config.base_dir().to_path_buf();
+        Arc::clone(
+            self.libraries
+                .entry(dir)
+                .or_insert_with(|| Arc::new(Library::load(config))),
+        )
+    }
+
+    pub fn modules(&self) -> Arc<ModuleGraph> {
+        Arc::clone(&self.modules)
+    }
+
+    pub fn symbols(&self) -> &SymbolIndex {
+        &self.symbols
+    }
+
+    /// Records that every module of `folder` is indexed, so that modules
+    /// nothing requires can be reported.
+    pub fn mark_indexed(&mut self, folder: &Path) {
+        Arc::make_mut(&mut self.modules).mark_indexed(folder);
+    }
+
+    /// Records the requires, exports and symbols of `code`, the content of
+    /// `path`, and reads the modules it depends on that are not indexed yet.
+    pub fn update_module(&mut self, path: &Path, code: &str, config: &Config, resolver: &Resolver) {
+        let modules = Arc::make_mut(&mut self.modules);
+        modules.update(path, code, config, resolver);
+        modules.load_dependencies(config, resolver);
+        let module = resolver.module_name(path);
+        self.symbols
+            .update(path, code, config.runtime.version, module.as_deref());
+    }
+
+    /// Indexes the symbols of the `workspace.library` files of `config` that
+    /// are not indexed yet.
+    pub fn index_library(&mut self, config: &Config, resolver: &Resolver) {
+        let library = self.library_for(config);
+        for path in library.files() {
+            if self.symbols.contains(path) {
+                continue;
+            }
+            match fs::read_to_string(path) {
+                Ok(code) => {
+                    let module = resolver.module_name(path);
+                    self.symbols
+                        .update(path, &code, config.runtime.version, module.as_deref());
+                }
+                Err(e) => event!(Level::INFO, "failed to read {:?}: {}", path, e),
+            }
+        }
+    }
+
+    /// Open or indexed documents that require `path` or are required by it.
+    pub fn related_documents(&self, path: &Path) -> Vec<(Url, String)> {
+        let dependencies = self
+            .modules
+            .get(path)
+            .into_iter()
+            .flat_map(|module| module.dependencies.iter().map(|d| d.path.as_path()));
+        let mut related: Vec<&Path> = self.modules.dependents(path).chain(dependencies).collect();
+        related.sort();
+        related.dedup();
+        related
+            .into_iter()
+            .filter_map(|path| {
+                let uri = Url::from_file_path(path).ok()?;
+                let doc = self.documents.get(&uri)?;
+                Some((uri, doc.text()))
+            })
+            .collect()
+    }
+
+    /// Indexes a file read from disk unless the client already opened it.
+    pub fn index_doc(&mut self, uri: Url, content: &str) {
+        self.documents
+            .entry(uri)
+            .or_insert_with(|| Document::new(content, None));
+    }
+
+    pub fn open_doc(&mut self, uri: Url, content: &str, version: i32) {
+        self.documents
+            .insert(uri, Document::new(content, Some(version)));
+    }
+
+    /// Applies `changes` in order and returns the new text. Changes whose
+    /// version is not newer than the stored one are rejected.
+    pub fn change_doc(
+        &mut self,
+        uri: &Url,
+        version: i32,
+        changes: &[TextDocumentContentChangeEvent],
+        encoding: PositionEncoding,
+    ) -> Result<String> {
+        let doc = self
+            .documents
+            .get_mut(uri)
+            .ok_or_else(|| LuascanError::UnknownDocument {
+                uri: uri.to_string(),
+            })?;
+        if let Some(current) = doc.version()
+            && version <= current
+        {
+            return Err(LuascanError::StaleDocumentVersion {
+                uri: uri.to_string(),
+                version,
+                current,
+            }
+            .into());
+        }
+        let mut updated = doc.clone();
+        for change in changes {
+            updated.apply_change(change, encoding).ok_or_else(|| {
+                LuascanError::InvalidChangeRange {
+                    uri: uri.to_string(),
+                }
+            })?;
+        }
+        updated.set_version(version);
+        let text = updated.text();
+        *doc = updated;
+        Ok(text)
+    }
+
+    /// Discards the unsaved edits of the closed document `uri`. A file of a
+    /// workspace folder or of a library goes back to its content on disk,
+    /// which is returned; any other document is forgotten.
+    pub fn close_doc(&mut self, uri: &Url) -> Option<String> {
+        let Ok(path) = uri.to_file_path() else {
+            self.documents.remove(uri);
+            return None;
+        };
+        let indexed = self.folders.iter().any(|f| path.starts_with(f))
+            || self
+                .config_for(&path)
+                .is_ok_and(|config| self.library_for(&config).contains(&path));
+        match fs::read_to_string(&path) {
+            Ok(content) if indexed => {
+                self.documents
+                    .insert(uri.clone(), Document::new(&content, None));
+                Some(content)
+            }
+            _ => {
+                self.documents.remove(uri);
+                Arc::make_mut(&mut self.modules).remove(&path);
+                self.symbols.remove(&path);
+                None
+            }
+        }
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = (&Url, &Document)> {
+        self.documents.iter()
+    }
+}
+
+/// Lists every Lua source below `dir`, sorted by path.
+pub fn lua_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let pattern = dir.join("**/*.lua");
+    let mut files = Vec::new();
+    for entry in glob(&pattern.to_string_lossy())? {
+        match entry {
+            Ok(p) if p.is_file() => files.push(p),
+            Ok(_) => {}
+            Err(e) => {
+                event!(Level::INFO, "glob error {:?}", e);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Expands config entries (files, directories or glob patterns relative to
+/// `base`) into the Lua sources they name, sorted by path.
+pub fn resolve_paths(base: &Path, entries: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = base.join(entry);
+        if entry.contains(['*', '?', '[']) {
+            for p in glob(&path.to_string_lossy())?.flatten() {
+                if p.is_dir() {
+                    files.extend(lua_files(&p)?);
+                } else if p.is_file() {
+                    files.push(p);
+                }
+            }
+        } else if path.is_dir() {
+            files.extend(lua_files(&path)?);
+        } else if path.is_file() {
+            files.push(path);
+        } else {
+            return Err(LuascanError::TargetNotFound { path }.into());
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
 #[cfg(test)]
 mod tests {
-    use glob::glob;
+    use super::*;
+    use crate::config::RuntimeVersion;
+    use pretty_assertions::assert_eq;
+
     #[test]
     fn test_glob() {
         for entry in glob("tests/**/*.lua").expect("failed to read glob patterns") {
@@ -10,4 +327,127 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_lua_files() {
+        let files = lua_files(Path::new("tests/sample_workspace")).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("tests/sample_workspace/init.lua"),
+                PathBuf::from("tests/sample_workspace/ui/init.lua"),
+                PathBuf::from("tests/sample_workspace/ui/utils.lua"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_paths() {
+        let base = Path::new("tests/library");
+        let entries = vec!["scripts/*.lua".to_string(), "vendor".to_string()];
+        assert_eq!(
+            resolve_paths(base, &entries).unwrap(),
+            vec![
+                PathBuf::from("tests/library/scripts/build.lua"),
+                PathBuf::from("tests/library/vendor/host.lua"),
+            ]
+        );
+        assert!(resolve_paths(base, &["missing".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_config_for_closest_ancestor() {
+        let mut workspace = Workspace::new(Config::default());
+        let config = workspace
+            .config_for(Path::new("tests/multi_root/lua54/main.lua"))
+            .unwrap();
+        assert!(matches!(config.runtime.version, RuntimeVersion::Lua54));
+        let config = workspace
+            .config_for(Path::new("tests/multi_root/lua51/lib/util.lua"))
+            .unwrap();
+        assert!(matches!(config.runtime.version, RuntimeVersion::Lua51));
+    }
+
+    #[test]
+    fn test_remove_folder() {
+        let mut workspace = Workspace::new(Config::default());
+        workspace.add_folder(PathBuf::from("/a"));
+        workspace.add_folder(PathBuf::from("/b"));
+        let x = Url::parse("file:///a/x.lua").unwrap();
+        let y = Url::parse("file:///b/y.lua").unwrap();
+        workspace.index_doc(x.clone(), "");
+        workspace.index_doc(y, "");
+        let removed = workspace.remove_folder(Path::new("/a"));
+        assert_eq!(removed, vec![x]);
+        assert_eq!(workspace.folders(), &[PathBuf::from("/b")]);
+        assert_eq!(workspace.documents().count(), 1);
+    }
+
+    #[test]
+    fn test_index_library_symbols() {
+        let mut workspace = Workspace::new(Config::default());
+        let folder = PathBuf::from("tests/library");
+        let config = workspace.config_for(&folder.join("src/main.lua")).unwrap();
+        let resolver = Resolver::new(&config, [folder]);
+        workspace.index_library(&config, &resolver);
+        let names: Vec<&str> = workspace
+            .symbols()
+            .search("host")
+            .into_iter()
+            .map(|(_, symbol)| symbol.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["host", "host.call"]);
+        assert!(workspace.symbols().search("main").is_empty());
+    }
+
+    #[test]
+    fn test_close_doc() {
+        let mut workspace = Workspace::new(Config::default());
+        let folder = fs::canonicalize("tests/modules").unwrap();
+        workspace.add_folder(folder.clone());
+        let config = Config::default();
+        let resolver = Resolver::new(&config, [folder.clone()]);
+        let ui = folder.join("ui.lua");
+        let uri = Url::from_file_path(&ui).unwrap();
+        workspace.open_doc(uri.clone(), "local M = {}\nreturn M\n", 2);
+        workspace.update_module(&ui, "local M = {}\nreturn M\n", &config, &resolver);
+        let on_disk = fs::read_to_string(&ui).unwrap();
+        assert_eq!(workspace.close_doc(&uri), Some(on_disk));
+        let (_, doc) = workspace.documents().next().unwrap();
+        assert_eq!(doc.version(), None);
+
+        let outside = fs::canonicalize("tests/sample_workspace/init.lua").unwrap();
+        let uri = Url::from_file_path(&outside).unwrap();
+        workspace.open_doc(uri.clone(), "x = 1\n", 1);
+        workspace.update_module(&outside, "x = 1\n", &config, &resolver);
+        assert!(workspace.symbols().contains(&outside));
+        assert_eq!(workspace.close_doc(&uri), None);
+        assert_eq!(workspace.documents().count(), 1);
+        assert!(workspace.modules().get(&outside).is_none());
+        assert!(!workspace.symbols().contains(&outside));
+    }
+
+    #[test]
+    fn test_change_doc_rejects_stale_version() {
+        let mut workspace = Workspace::new(Config::default());
+        let uri = Url::parse("file:///a/x.lua").unwrap();
+        workspace.open_doc(uri.clone(), "local x = 1\n", 3);
+        let full = |text: &str| TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: text.to_string(),
+        };
+        let text = workspace
+            .change_doc(&uri, 4, &[full("local y = 2\n")], PositionEncoding::Utf16)
+            .unwrap();
+        assert_eq!(text, "local y = 2\n");
+        assert!(
+            workspace
+                .change_doc(&uri, 4, &[full("local z = 3\n")], PositionEncoding::Utf16)
+                .is_err()
+        );
+        let (_, doc) = workspace.documents().next().unwrap();
+        assert_eq!(doc.version(), Some(4));
+        assert_eq!(doc.text(), "local y = 2\n");
+    }
 }
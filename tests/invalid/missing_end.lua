@@ -0,0 +1,4 @@
+local function greet(name)
+	print("hello, " .. name)
+
+greet("luascan")
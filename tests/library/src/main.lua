@@ -0,0 +1 @@
+return host.call(1)
@@ -0,0 +1,7 @@
+local b = require("b")
+
+return {
+    run = function()
+        return b.value
+    end,
+}
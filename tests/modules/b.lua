@@ -0,0 +1,10 @@
+local a = require("a")
+
+local M = {}
+M.value = 1
+
+function M.call()
+    return a.run()
+end
+
+return M
@@ -0,0 +1,6 @@
+local ui = require("ui")
+local a = require("a")
+
+ui.initalize_ui()
+ui.render(ui.theme)
+print(a.run())
@@ -0,0 +1,9 @@
+local M = { theme = "dark" }
+
+function M.initialize_ui() end
+
+M.render = function(theme)
+    print(theme)
+end
+
+return M
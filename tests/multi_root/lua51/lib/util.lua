@@ -0,0 +1,5 @@
+local M = {}
+function M.clamp(x, lo, hi)
+	return math.max(lo, math.min(x, hi))
+end
+return M
@@ -0,0 +1,2 @@
+local limit <const> = 10
+print(limit)